@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use completer::{ArgumentKind, Definition};
+use shell::Shell;
+use status::*;
+
+/// Defines how the arguments of an external command are completed.
+///
+/// - `complete COMMAND [SUBCOMMAND...] -s 'SUBCOMMANDS'`: subcommands of the command
+/// - `complete COMMAND [SUBCOMMAND...] -f 'FLAGS'`: flags of the command
+/// - `complete COMMAND [SUBCOMMAND...] -a file|directory|none`: the kind of arguments it accepts
+/// - `complete COMMAND [SUBCOMMAND...] -c 'CHOICES'`: arguments that are one of the given words
+/// - `complete COMMAND [SUBCOMMAND...] -F FUNCTION`: arguments that are printed by the function, which is
+///   given the words of the command line as its arguments
+/// - `complete -r COMMAND [SUBCOMMAND...]`: removes the definitions of the command
+///
+/// Lists of words are separated by whitespace, and each definition adds to those before it.
+/// Without any arguments, every definition is printed.
+pub fn complete(args: &[String], shell: &mut Shell) -> i32 {
+    if args.len() < 2 {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let mut commands: Vec<&String> = shell.completions.keys().collect();
+        commands.sort();
+        for command in commands {
+            let _ = writeln!(stdout, "{}", describe(command, &shell.completions[command]));
+        }
+        return SUCCESS;
+    }
+
+    match parse(&args[1..]) {
+        Ok(update) => {
+            update.apply(&mut shell.completions);
+            SUCCESS
+        },
+        Err(why) => {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: complete: {}", why);
+            FAILURE
+        }
+    }
+}
+
+/// A change to the definitions, as given by the arguments of `complete`.
+#[derive(Debug, Default, PartialEq)]
+struct Update {
+    /// The command, followed by any of its subcommands.
+    path:        Vec<String>,
+    remove:      bool,
+    subcommands: Vec<String>,
+    flags:       Vec<String>,
+    arguments:   Option<ArgumentKind>,
+}
+
+impl Update {
+    fn apply(self, definitions: &mut HashMap<String, Definition>) {
+        let key = self.path.join(" ");
+        if self.remove {
+            let prefix = format!("{} ", key);
+            let removed: Vec<String> = definitions.keys()
+                .filter(|command| **command == key || command.starts_with(&prefix))
+                .cloned()
+                .collect();
+            for command in removed {
+                definitions.remove(&command);
+            }
+            return
+        }
+
+        // Each subcommand within the path is added to the subcommands of its parent.
+        for depth in 1..self.path.len() {
+            let parent = definitions.entry(self.path[..depth].join(" ")).or_insert_with(Definition::default);
+            add_words(&mut parent.subcommands, &[self.path[depth].clone()]);
+        }
+
+        let definition = definitions.entry(key).or_insert_with(Definition::default);
+        add_words(&mut definition.subcommands, &self.subcommands);
+        add_words(&mut definition.flags, &self.flags);
+        if let Some(arguments) = self.arguments {
+            definition.arguments = arguments;
+        }
+    }
+}
+
+/// Appends the words which the list does not already contain.
+fn add_words(list: &mut Vec<String>, words: &[String]) {
+    for word in words {
+        if !list.contains(word) {
+            list.push(word.clone());
+        }
+    }
+}
+
+fn split(words: &str) -> Vec<String> {
+    words.split_whitespace().map(String::from).collect()
+}
+
+fn parse(args: &[String]) -> Result<Update, String> {
+    let mut update = Update::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            update.path.push(arg.clone());
+            continue
+        } else if arg == "-r" {
+            update.remove = true;
+            continue
+        }
+
+        let value = match args.next() {
+            Some(value) => value,
+            None        => return Err(format!("option requires an argument: {}", arg))
+        };
+        match arg.as_str() {
+            "-s" => update.subcommands.extend(split(value)),
+            "-f" => update.flags.extend(split(value)),
+            "-c" => update.arguments = Some(ArgumentKind::Choices(split(value))),
+            "-F" => update.arguments = Some(ArgumentKind::Function(value.clone())),
+            "-a" => update.arguments = Some(match value.as_str() {
+                "file"      => ArgumentKind::File,
+                "directory" => ArgumentKind::Directory,
+                "none"      => ArgumentKind::Nothing,
+                _           => return Err(format!("unknown kind of argument: {}", value))
+            }),
+            _ => return Err(format!("unknown option: {}", arg))
+        }
+    }
+
+    if update.path.is_empty() {
+        Err("no command was given".to_owned())
+    } else {
+        Ok(update)
+    }
+}
+
+/// Describes the definition as the `complete` command which would create it.
+fn describe(command: &str, definition: &Definition) -> String {
+    let mut description = format!("complete {}", command);
+    if !definition.subcommands.is_empty() {
+        description.push_str(&format!(" -s '{}'", definition.subcommands.join(" ")));
+    }
+    if !definition.flags.is_empty() {
+        description.push_str(&format!(" -f '{}'", definition.flags.join(" ")));
+    }
+    match definition.arguments {
+        ArgumentKind::File                 => (),
+        ArgumentKind::Directory            => description.push_str(" -a directory"),
+        ArgumentKind::Nothing              => description.push_str(" -a none"),
+        ArgumentKind::Choices(ref choices) => description.push_str(&format!(" -c '{}'", choices.join(" "))),
+        ArgumentKind::Function(ref name)   => description.push_str(&format!(" -F {}", name)),
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use completer::find_definition;
+    use test_helpers::strings;
+
+    fn find(definitions: &HashMap<String, Definition>, words: &[&str]) -> Option<ArgumentKind> {
+        find_definition(definitions, &strings(words)).map(|definition| definition.arguments)
+    }
+
+    fn define(definitions: &mut HashMap<String, Definition>, args: &[&str]) {
+        parse(&strings(args)).unwrap().apply(definitions);
+    }
+
+    #[test]
+    fn defining_completions() {
+        let mut definitions = HashMap::new();
+        define(&mut definitions, &["git", "-s", "add commit", "-f", "--help", "-a", "none"]);
+        define(&mut definitions, &["git", "remote", "add", "-f", "-t -m"]);
+        define(&mut definitions, &["git", "commit", "-f", "--amend"]);
+        define(&mut definitions, &["git", "-f", "--version --help"]);
+
+        assert_eq!(definitions["git"], Definition {
+            subcommands: strings(&["add", "commit", "remote"]),
+            flags:       strings(&["--help", "--version"]),
+            arguments:   ArgumentKind::Nothing,
+        });
+        assert_eq!(definitions["git remote"].subcommands, strings(&["add"]));
+        assert_eq!(definitions["git remote add"].flags, strings(&["-t", "-m"]));
+        assert_eq!(describe("git commit", &definitions["git commit"]), "complete git commit -f '--amend'");
+
+        define(&mut definitions, &["-r", "git", "remote"]);
+        assert!(!definitions.contains_key("git remote") && !definitions.contains_key("git remote add"));
+        assert!(definitions.contains_key("git"));
+    }
+
+    #[test]
+    fn finding_definitions() {
+        let mut definitions = HashMap::new();
+        define(&mut definitions, &["git", "-s", "add", "-a", "none"]);
+        define(&mut definitions, &["git", "commit", "-F", "commits"]);
+        define(&mut definitions, &["tool", "-c", "one two"]);
+
+        assert_eq!(find(&definitions, &["git"]), Some(ArgumentKind::Nothing));
+        assert_eq!(find(&definitions, &["git", "--verbose", "commit"]),
+            Some(ArgumentKind::Function("commits".to_owned())));
+        assert_eq!(find(&definitions, &["git", "add"]), Some(ArgumentKind::File));
+        assert_eq!(find(&definitions, &["git", "log", "commit"]), Some(ArgumentKind::Nothing));
+        assert_eq!(find(&definitions, &["tool", "one"]), Some(ArgumentKind::Choices(strings(&["one", "two"]))));
+        assert_eq!(find(&definitions, &["cargo"]), None);
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        assert!(parse(&strings(&["-s", "add"])).is_err());
+        assert!(parse(&strings(&["git", "-s"])).is_err());
+        assert!(parse(&strings(&["git", "-a", "socket"])).is_err());
+        assert!(parse(&strings(&["git", "-z", "one"])).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::io::{self, Write};
+
+use shell::Shell;
+use status::*;
+use sys::is_executable;
+
+/// Evaluates whether the supplied file, directory, string, variable, array or function exists,
+/// without needing to spawn an external `test` process.
+///
+/// - `exists -f FILE`: FILE is a regular file
+/// - `exists -d DIR`: DIR is a directory
+/// - `exists -e PATH`: PATH exists
+/// - `exists -x FILE`: FILE is executable
+/// - `exists -s STRING`: STRING is not empty (also the default when no flag is given)
+/// - `exists -v NAME`: a variable named NAME is defined
+/// - `exists -a NAME`: an array named NAME is defined
+/// - `exists --fn NAME`: a function named NAME is defined
+pub fn exists(args: &[String], shell: &Shell) -> i32 {
+    let (flag, value) = match (args.get(1), args.get(2)) {
+        (Some(flag), Some(value)) if flag.starts_with('-') => (flag.as_str(), value.as_str()),
+        (Some(value), None) => ("-s", value.as_str()),
+        (None, None) => return FAILURE,
+        _ => {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: exists: too many arguments");
+            return FAILURE
+        }
+    };
+
+    let exists = match flag {
+        "-f"   => fs::metadata(value).map(|metadata| metadata.is_file()).unwrap_or(false),
+        "-d"   => fs::metadata(value).map(|metadata| metadata.is_dir()).unwrap_or(false),
+        "-e"   => fs::metadata(value).is_ok(),
+        "-x"   => is_executable(value),
+        "-s"   => !value.is_empty(),
+        "-v"   => shell.variables.get_var(value).is_some(),
+        "-a"   => shell.variables.get_array(value).is_some(),
+        "--fn" => shell.functions.contains_key(value),
+        _ => {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: exists: invalid flag: {}", flag);
+            return FAILURE
+        }
+    };
+
+    if exists { SUCCESS } else { FAILURE }
+}
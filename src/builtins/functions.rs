@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use flow_control::Function;
+use status::*;
+
+/// Formats the function's signature, followed by its description if one was supplied.
+pub fn describe(function: &Function) -> String {
+    let mut output = function.name.clone();
+    for arg in &function.args {
+        output.push(' ');
+        output.push_str(arg);
+    }
+
+    if !function.description.is_empty() {
+        output.push_str(" -- ");
+        output.push_str(&function.description);
+    }
+    output
+}
+
+/// Lists every function that has been defined, along with their parameters and descriptions.
+pub fn fn_(functions: &HashMap<String, Function>) -> i32 {
+    let mut functions = functions.values().collect::<Vec<&Function>>();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let _ = buffer.write_all(b"# Functions\n");
+    for function in functions {
+        let _ = writeln!(buffer, "    {}", describe(function));
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = stdout.write_all(&buffer);
+    SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_function() {
+        let mut function = Function {
+            name:        "greet".to_owned(),
+            args:        vec!["first".to_owned(), "last".to_owned()],
+            description: String::new(),
+            statements:  Vec::new(),
+        };
+        assert_eq!("greet first last", describe(&function));
+
+        function.description = "Greets a person by name".to_owned();
+        assert_eq!("greet first last -- Greets a person by name", describe(&function));
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use shell::Shell;
+use status::*;
+use sys::is_executable;
+
+/// What a command name refers to, in the order that the shell resolves them.
+enum Resolution {
+    Alias(String),
+    Builtin,
+    Function,
+    Path(PathBuf),
+}
+
+fn resolve(name: &str, shell: &mut Shell) -> Option<Resolution> {
+    if let Some(alias) = shell.variables.aliases.get(name) {
+        Some(Resolution::Alias(alias.clone()))
+    } else if shell.builtins.contains_key(name) {
+        Some(Resolution::Builtin)
+    } else if shell.functions.contains_key(name) {
+        Some(Resolution::Function)
+    } else if name.contains('/') {
+        if is_executable(name) { Some(Resolution::Path(PathBuf::from(name))) } else { None }
+    } else {
+        shell.command_hash.get(name).cloned().map(Resolution::Path)
+    }
+}
+
+/// Prints the path of each command, or what it refers to if it is not an external command.
+pub fn which(args: &[String], shell: &mut Shell) -> i32 {
+    describe(args, shell, "which", |name, resolution| match resolution {
+        Resolution::Alias(alias) => format!("{}: aliased to {}", name, alias),
+        Resolution::Builtin      => format!("{}: built-in shell command", name),
+        Resolution::Function     => format!("{}: function", name),
+        Resolution::Path(path)   => path.to_string_lossy().into_owned(),
+    })
+}
+
+/// Prints what each command refers to, which is either an alias, builtin, function, or a path.
+pub fn type_(args: &[String], shell: &mut Shell) -> i32 {
+    describe(args, shell, "type", |name, resolution| match resolution {
+        Resolution::Alias(alias) => format!("{} is aliased to `{}`", name, alias),
+        Resolution::Builtin      => format!("{} is a shell builtin", name),
+        Resolution::Function     => format!("{} is a function", name),
+        Resolution::Path(path)   => format!("{} is {}", name, path.display()),
+    })
+}
+
+fn describe<F>(args: &[String], shell: &mut Shell, builtin: &str, description: F) -> i32
+    where F: Fn(&str, Resolution) -> String
+{
+    if args.len() < 2 {
+        let stderr = io::stderr();
+        let _ = writeln!(stderr.lock(), "ion: {}: no command was given", builtin);
+        return FAILURE;
+    }
+
+    let mut status = SUCCESS;
+    for name in &args[1..] {
+        match resolve(name, shell) {
+            Some(resolution) => println!("{}", description(name, resolution)),
+            None => {
+                let stderr = io::stderr();
+                let _ = writeln!(stderr.lock(), "ion: {}: {}: not found", builtin, name);
+                status = FAILURE;
+            }
+        }
+    }
+    status
+}
+
+/// Manages the table of commands found within `$PATH`.
+///
+/// - `hash`: prints every command within the table, along with its path
+/// - `hash -r`: forgets every command, so that `$PATH` will be searched again
+/// - `hash NAME...`: checks that each command can be found within `$PATH`
+pub fn hash(args: &[String], shell: &mut Shell) -> i32 {
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("-r") => {
+            shell.command_hash.clear();
+            SUCCESS
+        },
+        Some(_) => {
+            let mut status = SUCCESS;
+            for name in &args[1..] {
+                if shell.command_hash.get(name).is_none() {
+                    let stderr = io::stderr();
+                    let _ = writeln!(stderr.lock(), "ion: hash: {}: not found", name);
+                    status = FAILURE;
+                }
+            }
+            status
+        },
+        None => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let commands = shell.command_hash.commands();
+            let mut names: Vec<&String> = commands.keys().collect();
+            names.sort();
+            for name in names {
+                let _ = writeln!(stdout, "{}\t{}", name, commands[name].display());
+            }
+            SUCCESS
+        }
+    }
+}
@@ -1,7 +1,17 @@
+pub mod complete;
+pub mod exists;
+pub mod functions;
+pub mod lookup;
+pub mod set;
 pub mod source;
 pub mod variables;
 
+use self::complete::complete;
+use self::exists::exists;
+use self::functions::{describe, fn_};
+use self::lookup::{hash, type_, which};
 use self::variables::{alias, drop_alias, drop_variable, export_variable};
+use self::set::set;
 use self::source::source;
 
 use std::collections::HashMap;
@@ -128,9 +138,10 @@ impl Builtin {
         commands.insert("read",
                         Builtin {
                             name: "read",
-                            help: "Read some variables\n    read <variable>",
+                            help: "Read some variables\n    read [-0] <variable>",
                             main: box |args: &[String], shell: &mut Shell| -> i32 {
-                                shell.variables.read(args)
+                                let input = shell.stdin.take();
+                                shell.variables.read(args, input)
                             },
                         });
 
@@ -154,12 +165,32 @@ impl Builtin {
                     },
                 });
 
+        commands.insert("fn",
+                        Builtin {
+                            name: "fn",
+                            help: "Print list of functions with their parameters and descriptions",
+                            main: box |_: &[String], shell: &mut Shell| -> i32 {
+                                fn_(&shell.functions)
+                            },
+                        });
+
         commands.insert("history",
                         Builtin {
                             name: "history",
-                            help: "Display a log of all commands previously executed",
+                            help: "Display, search, or alter the log of commands previously executed\n    \
+                                   history [N | --search PATTERN | --delete N | --clear]",
+                            main: box |args: &[String], shell: &mut Shell| -> i32 {
+                                shell.history(args)
+                            },
+                        });
+
+        commands.insert("set",
+                        Builtin {
+                            name: "set",
+                            help: "Set or unset options of the shell, or print them if none are given\n    \
+                                   set [-e|+e] [-u|+u] [-x|+x] [-o|+o <errexit|nounset|xtrace>]",
                             main: box |args: &[String], shell: &mut Shell| -> i32 {
-                                shell.print_history(args)
+                                set(args, shell)
                             },
                         });
 
@@ -181,6 +212,56 @@ impl Builtin {
                             },
                         });
 
+        commands.insert("complete",
+                        Builtin {
+                            name: "complete",
+                            help: "Define how the arguments of a command are completed, or list the definitions\n    \
+                                   complete [-r] <command> [subcommand...] [-s <subcommands>] [-f <flags>] \
+                                   [-a file|directory|none] [-c <choices>] [-F <function>]",
+                            main: box |args: &[String], shell: &mut Shell| -> i32 {
+                                complete(args, shell)
+                            },
+                        });
+
+        commands.insert("hash",
+                        Builtin {
+                            name: "hash",
+                            help: "List the commands found within $PATH, or forget them with -r\n    \
+                                   hash [-r | <command>...]",
+                            main: box |args: &[String], shell: &mut Shell| -> i32 {
+                                hash(args, shell)
+                            },
+                        });
+
+        commands.insert("which",
+                        Builtin {
+                            name: "which",
+                            help: "Print the path of each command, or what it refers to\n    which <command>...",
+                            main: box |args: &[String], shell: &mut Shell| -> i32 {
+                                which(args, shell)
+                            },
+                        });
+
+        commands.insert("type",
+                        Builtin {
+                            name: "type",
+                            help: "Describe whether each command is an alias, builtin, function, or file\n    \
+                                   type <command>...",
+                            main: box |args: &[String], shell: &mut Shell| -> i32 {
+                                type_(args, shell)
+                            },
+                        });
+
+        commands.insert("exists",
+                        Builtin {
+                            name: "exists",
+                            help: "Check whether a file, directory, string, variable or function exists\n    \
+                                   exists [-f|-d|-e|-x|-s|-v|-a|--fn] <value>",
+                            main: box |args: &[String], shell: &mut Shell| -> i32 {
+                                exists(args, shell)
+                            },
+                        });
+
         commands.insert("true",
                         Builtin {
                             name: "true",
@@ -208,20 +289,21 @@ impl Builtin {
         commands.insert("help",
                         Builtin {
                             name: "help",
-                            help: "Display helpful information about a given command, or list \
-                                   commands if none specified\n    help <command>",
-                            main: box move |args: &[String], _: &mut Shell| -> i32 {
+                            help: "Display helpful information about a given command or function, \
+                                   or list commands if none specified\n    help <command>",
+                            main: box move |args: &[String], shell: &mut Shell| -> i32 {
                                 let stdout = io::stdout();
                                 let mut stdout = stdout.lock();
                                 if let Some(command) = args.get(1) {
-                                    if command_helper.contains_key(command.as_str()) {
-                                        if let Some(help) = command_helper.get(command.as_str()) {
-                                            let _ = stdout.write_all(help.as_bytes());
-                                            let _ = stdout.write_all(b"\n");
-                                        }
+                                    if let Some(help) = command_helper.get(command.as_str()) {
+                                        let _ = stdout.write_all(help.as_bytes());
+                                        let _ = stdout.write_all(b"\n");
+                                    } else if let Some(function) = shell.functions.get(command.as_str()) {
+                                        let _ = writeln!(stdout, "{}", describe(function));
+                                    } else {
+                                        let _ = stdout.write_all(b"Command helper not found [run 'help']...");
+                                        let _ = stdout.write_all(b"\n");
                                     }
-                                    let _ = stdout.write_all(b"Command helper not found [run 'help']...");
-                                    let _ = stdout.write_all(b"\n");
                                 } else {
                                     let mut commands = command_helper.keys().cloned().collect::<Vec<&str>>();
                                     commands.sort();
@@ -0,0 +1,102 @@
+use std::io::{self, Write};
+
+use shell::Shell;
+use shell::flags::*;
+use status::*;
+
+/// Enables options given with a `-`, and disables options given with a `+`.
+///
+/// - `set -e` / `set -o errexit`: exit the shell when a pipeline fails
+/// - `set -u` / `set -o nounset`: treat the expansion of an undefined variable as an error
+/// - `set -x` / `set -o xtrace`: print each pipeline after it has been expanded
+///
+/// Without any arguments, the state of each option is printed.
+pub fn set(args: &[String], shell: &mut Shell) -> i32 {
+    if args.len() < 2 {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        for &(_, name, flag) in OPTIONS.iter() {
+            let state = if shell.flags & flag != 0 { "on" } else { "off" };
+            let _ = writeln!(stdout, "{:8} {}", name, state);
+        }
+        return SUCCESS;
+    }
+
+    match update_flags(shell.flags, &args[1..]) {
+        Ok(flags) => {
+            shell.flags = flags;
+            SUCCESS
+        },
+        Err(why) => {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: set: {}", why);
+            FAILURE
+        }
+    }
+}
+
+/// Applies the given arguments to the flags, returning the updated flags.
+fn update_flags(mut flags: u8, args: &[String]) -> Result<u8, String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let enable = match arg.chars().next() {
+            Some('-') => true,
+            Some('+') => false,
+            _         => return Err(format!("invalid argument: {}", arg))
+        };
+
+        let mut options = Vec::new();
+        if &arg[1..] == "o" {
+            let name = match args.next() {
+                Some(name) => name,
+                None       => return Err(format!("option requires an argument: {}", arg))
+            };
+            match OPTIONS.iter().find(|&&(_, long, _)| long == name.as_str()) {
+                Some(&(_, _, flag)) => options.push(flag),
+                None                => return Err(format!("unknown option: {}", name))
+            }
+        } else if arg.len() > 1 {
+            for short in arg.chars().skip(1) {
+                match from_short(short) {
+                    Some(flag) => options.push(flag),
+                    None       => return Err(format!("unknown option: {}{}", &arg[..1], short))
+                }
+            }
+        } else {
+            return Err(format!("invalid argument: {}", arg));
+        }
+
+        for flag in options {
+            if enable { flags |= flag } else { flags &= !flag }
+        }
+    }
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(flags: u8, args: &[&str]) -> Result<u8, String> {
+        let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+        update_flags(flags, &args)
+    }
+
+    #[test]
+    fn enabling_and_disabling_options() {
+        assert_eq!(update(0, &["-e"]), Ok(ERR_EXIT));
+        assert_eq!(update(0, &["-eux"]), Ok(ERR_EXIT | NO_UNSET | PRINT_COMMS));
+        assert_eq!(update(ERR_EXIT | PRINT_COMMS, &["+x", "-u"]), Ok(ERR_EXIT | NO_UNSET));
+        assert_eq!(update(0, &["-o", "nounset", "-o", "xtrace"]), Ok(NO_UNSET | PRINT_COMMS));
+        assert_eq!(update(NO_UNSET, &["+o", "nounset"]), Ok(0));
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(update(0, &["-z"]).is_err());
+        assert!(update(0, &["-o"]).is_err());
+        assert!(update(0, &["-o", "unknown"]).is_err());
+        assert!(update(0, &["e"]).is_err());
+        assert!(update(0, &["-"]).is_err());
+    }
+}
@@ -11,12 +11,7 @@ pub fn source(shell: &mut Shell, arguments: &[String]) -> Result<(), String> {
                 let mut command_list = String::with_capacity(capacity);
                 file.read_to_string(&mut command_list)
                     .map_err(|message| format!("ion: {}: failed to read {}\n", message, argument))
-                    .map(|_| {
-                        for command in command_list.lines() {
-                            shell.on_command(command);
-                        }
-                        ()
-                    })
+                    .map(|_| shell.execute_script(&command_list, argument))
             } else {
                 Err(format!("ion: failed to open {}\n", argument))
             }
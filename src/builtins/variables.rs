@@ -174,7 +174,7 @@ pub fn drop_variable<I: IntoIterator>(vars: &mut Variables, args: I) -> i32
         return FAILURE;
     }
     for variable in args.iter().skip(1) {
-        if vars.unset_var(variable.as_ref()).is_none() {
+        if !vars.unset_var(variable.as_ref()) {
             let stderr = io::stderr();
             let _ = writeln!(&mut stderr.lock(), "ion: undefined variable: {}", variable.as_ref());
             return FAILURE;
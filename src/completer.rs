@@ -1,25 +1,532 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
 use liner::Completer;
 
-/// A completer that combines suggestions from multiple completers.
-#[derive(Clone, Eq, PartialEq)]
-pub struct MultiCompleter<A, B> where A: Completer, B: Completer {
-    a: Vec<A>,
-    b: B
+use parser::{pipelines, StatementSplitter};
+use parser::shell_expand::{WordIterator, WordToken};
+use shell::JobKind;
+
+/// How the candidates for completion are matched against the text being completed, as given by
+/// the `COMPLETION_MATCHING` variable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Matching {
+    /// Candidates must begin with the text, which is the default.
+    Prefix,
+    /// Candidates may contain the text anywhere.
+    Substring,
+    /// Candidates may contain the characters of the text in order, with others between them.
+    Fuzzy,
+}
+
+impl Matching {
+    pub fn parse(value: &str) -> Matching {
+        match value {
+            "substring" => Matching::Substring,
+            "fuzzy"     => Matching::Fuzzy,
+            _           => Matching::Prefix,
+        }
+    }
+}
+
+/// Ranks how closely the candidate matches the text, where lower ranks are better, or returns
+/// `None` if it does not match at all. Candidates beginning with the text are ranked first,
+/// followed by those where the text begins at a word boundary, such as after a `_` or `/`, then
+/// those containing the text elsewhere, and lastly those containing its characters in order.
+/// Matches other than prefixes ignore case.
+pub fn rank(candidate: &str, text: &str, matching: Matching) -> Option<u8> {
+    if candidate.starts_with(text) { return Some(0) }
+    if matching == Matching::Prefix { return None }
+
+    let original: Vec<char> = candidate.chars().collect();
+    let candidate: Vec<char> = original.iter().map(|&c| lowercase(c)).collect();
+    let text: Vec<char> = text.chars().map(lowercase).collect();
+    let matches_at = |start: usize| candidate[start..].starts_with(&text);
+
+    let mut substring = false;
+    for start in 0..candidate.len() {
+        if !matches_at(start) { continue }
+        let boundary = start == 0 || match original[start - 1] {
+            '/' | '_' | '-' | '.' | ' ' => true,
+            previous => previous.is_lowercase() && original[start].is_uppercase()
+        };
+        if boundary { return Some(1) }
+        substring = true;
+    }
+    if substring { return Some(2) }
+
+    if matching == Matching::Fuzzy {
+        let mut characters = candidate.iter();
+        if text.iter().all(|c| characters.any(|candidate| candidate == c)) { return Some(3) }
+    }
+    None
+}
+
+fn lowercase(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+/// Where the candidates for completion are obtained from.
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    Words(Vec<String>),
+    /// Paths relative to the current directory, which may be limited to directories.
+    Paths { directories: bool },
+}
+
+/// Completes the candidates from each source which match the text, ordered by their rank.
+pub struct RankedCompleter {
+    sources:  Vec<Source>,
+    matching: Matching,
+}
+
+impl RankedCompleter {
+    pub fn new(sources: Vec<Source>, matching: Matching) -> RankedCompleter {
+        RankedCompleter { sources: sources, matching: matching }
+    }
+}
+
+impl Completer for RankedCompleter {
+    fn completions(&self, start: &str) -> Vec<String> {
+        let mut ranked = Vec::new();
+        for source in &self.sources {
+            match *source {
+                Source::Words(ref words) => for word in words {
+                    if let Some(rank) = rank(word, start, self.matching) {
+                        ranked.push((rank, word.clone()));
+                    }
+                },
+                Source::Paths { directories } => path_candidates(start, directories, self.matching, &mut ranked),
+            }
+        }
+        ranked.sort();
+        ranked.dedup();
+        ranked.into_iter().map(|(_, completion)| completion).collect()
+    }
 }
-impl<A, B> MultiCompleter<A, B> where A: Completer, B: Completer {
-    pub fn new(a: Vec<A>, b: B) -> MultiCompleter<A, B> {
-        MultiCompleter {
-            a: a,
-            b: b
+
+/// Ranks the entries of the directory named by the path, against the final component of the path.
+/// Whitespace within paths is escaped with a backslash, and a `~` refers to the home directory.
+fn path_candidates(path: &str, directories: bool, matching: Matching, ranked: &mut Vec<(u8, String)>) {
+    let (directory, name) = match path.rfind('/') {
+        Some(position) => path.split_at(position + 1),
+        None           => ("", path),
+    };
+    let name = name.replace("\\ ", " ");
+
+    let search = if directory.is_empty() {
+        PathBuf::from(".")
+    } else if directory.starts_with("~/") {
+        match env::home_dir() {
+            Some(home) => home.join(directory[2..].replace("\\ ", " ")),
+            None       => return
+        }
+    } else {
+        PathBuf::from(directory.replace("\\ ", " "))
+    };
+
+    let entries = match fs::read_dir(search) {
+        Ok(entries) => entries,
+        Err(_)      => return
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let entry_name = match entry.file_name().into_string() {
+            Ok(entry_name) => entry_name,
+            Err(_)         => continue
+        };
+        // Hidden files are only completed when they have been asked for.
+        if entry_name.starts_with('.') && !name.starts_with('.') { continue }
+
+        let is_dir = entry.path().is_dir();
+        if directories && !is_dir { continue }
+        if let Some(rank) = rank(&entry_name, &name, matching) {
+            let mut completion = [directory, &entry_name.replace(" ", "\\ ")].concat();
+            if is_dir { completion.push('/'); }
+            ranked.push((rank, completion));
         }
     }
 }
-impl<A, B> Completer for MultiCompleter<A, B> where A: Completer, B: Completer {
+
+/// Completes the token at the cursor, rather than the word given by liner. Liner separates words
+/// by whitespace alone, so the word `ls|gr` must only have `gr` completed, whereas within quotes,
+/// the token `"foo b` spans more than the word `b`.
+pub struct TokenCompleter {
+    token: String,
+    inner: Box<Completer>
+}
+
+impl TokenCompleter {
+    pub fn new(token: String, inner: Box<Completer>) -> TokenCompleter {
+        TokenCompleter { token: token, inner: inner }
+    }
+}
+
+impl Completer for TokenCompleter {
     fn completions(&self, start: &str) -> Vec<String> {
-        let mut completions = self.b.completions(start);
-        for x in &self.a {
-            completions.extend_from_slice(&x.completions(start));
+        let token = self.token.as_str();
+        if start.ends_with(token) {
+            let head = &start[..start.len() - token.len()];
+            self.inner.completions(token).into_iter()
+                .map(|completion| format!("{}{}", head, completion))
+                .collect()
+        } else if token.ends_with(start) {
+            let head = &token[..token.len() - start.len()];
+            self.inner.completions(token).into_iter()
+                .filter(|completion| completion.starts_with(head))
+                .map(|completion| completion[head.len()..].to_owned())
+                .collect()
+        } else {
+            self.inner.completions(start)
         }
-        completions
+    }
+}
+
+/// Reads the names of the users of the system, which may follow a `~`.
+pub fn user_names() -> Vec<String> {
+    match File::open("/etc/passwd") {
+        Ok(file) => BufReader::new(file).lines()
+            .filter_map(Result::ok)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split(':').next().map(String::from))
+            .collect(),
+        Err(_) => Vec::new()
+    }
+}
+
+/// The kind of argument which a command accepts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgumentKind {
+    File,
+    Directory,
+    /// One of the given words.
+    Choices(Vec<String>),
+    /// One of the lines printed by the named function.
+    Function(String),
+    /// Nothing besides the subcommands and flags of the command.
+    Nothing,
+}
+
+/// How the arguments of a command, or of one of its subcommands, are completed, as defined by the
+/// `complete` builtin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub subcommands: Vec<String>,
+    pub flags:       Vec<String>,
+    pub arguments:   ArgumentKind,
+}
+
+impl Default for Definition {
+    fn default() -> Definition {
+        Definition { subcommands: Vec::new(), flags: Vec::new(), arguments: ArgumentKind::File }
+    }
+}
+
+impl Definition {
+    /// The sources of completions for the token, which are flags if the token begins with a `-`,
+    /// and otherwise the subcommands and arguments. As the shell is needed to run a function, the
+    /// lines printed by the function of `ArgumentKind::Function` must be given as `output`.
+    pub fn sources(&self, token: &str, output: Vec<String>) -> Vec<Source> {
+        if token.starts_with('-') { return vec![Source::Words(self.flags.clone())] }
+
+        let mut words = self.subcommands.clone();
+        let paths = match self.arguments {
+            ArgumentKind::File      => Some(Source::Paths { directories: false }),
+            ArgumentKind::Directory => Some(Source::Paths { directories: true }),
+            ArgumentKind::Choices(ref choices) => {
+                words.extend(choices.iter().cloned());
+                None
+            },
+            ArgumentKind::Function(_) => {
+                words.extend(output);
+                None
+            },
+            ArgumentKind::Nothing => None,
+        };
+
+        let mut sources = vec![Source::Words(words)];
+        sources.extend(paths);
+        sources
+    }
+}
+
+/// Finds the definition for the words of a command, which are keyed by the command along with any
+/// of its subcommands, such as `git remote`. Subcommands are followed until an argument is found
+/// which is neither a flag nor a subcommand.
+pub fn find_definition(definitions: &HashMap<String, Definition>, words: &[String]) -> Option<Definition> {
+    let mut path = match words.first() {
+        Some(command) => command.clone(),
+        None          => return None
+    };
+    let mut definition = match definitions.get(&path) {
+        Some(definition) => definition,
+        None             => return None
+    };
+
+    for word in &words[1..] {
+        if word.starts_with('-') { continue }
+        if !definition.subcommands.contains(word) { break }
+        let subcommand = format!("{} {}", path, word);
+        match definitions.get(&subcommand) {
+            Some(next) => definition = next,
+            // Subcommands which have not been given a definition of their own complete paths.
+            None       => return Some(Definition::default()),
+        }
+        path = subcommand;
+    }
+    Some(definition.clone())
+}
+
+/// What is expected of the token at the cursor, as determined by the words which precede it.
+#[derive(Debug, PartialEq)]
+pub enum Context {
+    /// The name of a command, which begins each pipeline, and follows `|`, `&&`, `||`, `;`, `&`,
+    /// `$(`, and the keywords that introduce a condition.
+    Command,
+    /// An argument of a command, where the words of the command which precede it are given.
+    Argument(Vec<String>),
+    /// A directory, as given to `cd` and `pushd`.
+    Directory,
+    /// A file that the input or output of a command is redirected from or to.
+    Redirection,
+    /// The name of a variable, following a `$`.
+    Variable,
+    /// The name of an array, following a `@`.
+    Array,
+    /// The name of a user, following a `~`.
+    User,
+    /// A single-quoted string, within which nothing may be completed.
+    Literal,
+}
+
+/// Determines the context of the token at the end of the given line, which holds the text before
+/// the cursor, and returns it along with the token that should be completed. The line is divided
+/// with the same tokenizers that the shell uses to execute it: statements by the
+/// `StatementSplitter`, words and redirections by `pipelines::collect`, and expansions by the
+/// `WordIterator`.
+pub fn context(line: &str) -> (Context, &str) {
+    // The cursor is within the last statement, unless that statement has been ended.
+    let mut splitter = StatementSplitter::new(line);
+    let mut start = 0;
+    loop {
+        let from = splitter.offset();
+        match splitter.next() {
+            Some(Ok(statement)) => {
+                let end = statement.as_ptr() as usize - line.as_ptr() as usize + statement.len();
+                let rest = line[end..splitter.offset()].trim_left();
+                start = if rest.starts_with(';') { splitter.offset() } else { from };
+                // Nothing may be completed within a comment.
+                if rest.starts_with('#') { return (Context::Literal, &line[line.len()..]) }
+            },
+            Some(Err(_)) => start = from,
+            None => break
+        }
+    }
+    statement_context(&line[start..])
+}
+
+/// Determines the context of the token at the end of the statement.
+fn statement_context(statement: &str) -> (Context, &str) {
+    let mut error = None;
+    let pipeline = pipelines::collect(&mut error, statement);
+    // The words of the command at the cursor, which has yet to begin if the final job was ended.
+    let mut words: Vec<String> = match pipeline.jobs.last() {
+        Some(job) if job.kind == JobKind::Last => {
+            job.args.iter().flat_map(|arg| split_words(arg)).map(String::from).collect()
+        },
+        _ => Vec::new()
+    };
+    let tail = |word: &str| if !word.is_empty() && statement.ends_with(word) {
+        let start = statement.len() - word.len();
+        if statement.is_char_boundary(start) { Some(&statement[start..]) } else { None }
+    } else {
+        None
+    };
+
+    // A redirection ends the pipeline, so the token is either the file that follows it, or the
+    // final word of the command.
+    let redirection = pipeline.stdout.iter().chain(pipeline.stdin.iter())
+        .filter_map(|redirection| tail(&redirection.file))
+        .next();
+    let word = match redirection {
+        _ if statement.ends_with(char::is_whitespace) => None,
+        Some(file) => {
+            // Within a condition, `<` and `>` are comparisons rather than redirections.
+            if is_condition(&words) { return word_context(words, file) }
+            return (Context::Redirection, file)
+        },
+        None => words.last().and_then(|word| tail(word))
+    };
+    match word {
+        Some(word) => {
+            words.pop();
+            word_context(words, word)
+        },
+        None if error.is_some() && !is_condition(&words) => (Context::Redirection, &statement[statement.len()..]),
+        None => word_context(words, &statement[statement.len()..])
+    }
+}
+
+/// Divides the argument wherever it contains whitespace which is not quoted, as the words which
+/// follow a process expansion are collected into the same argument as the expansion.
+fn split_words(argument: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    for token in WordIterator::new(argument) {
+        if let WordToken::Whitespace(whitespace) = token {
+            let offset = whitespace.as_ptr() as usize - argument.as_ptr() as usize;
+            words.push(&argument[start..offset]);
+            start = offset + whitespace.len();
+        }
+    }
+    if start != argument.len() { words.push(&argument[start..]); }
+    words
+}
+
+/// Determines the context of the word, given the words of the command which precede it. Variables,
+/// arrays, and process expansions are found by the `WordIterator`, as the word would be expanded.
+fn word_context(words: Vec<String>, word: &str) -> (Context, &str) {
+    let end = word.as_ptr() as usize + word.len();
+    let ends_word = |text: &str| text.as_ptr() as usize + text.len() == end;
+    match WordIterator::new(word).last() {
+        Some(WordToken::Variable(name, _)) if ends_word(name) => return (Context::Variable, name),
+        Some(WordToken::ArrayVariable(name, ..)) if ends_word(name) => return (Context::Array, name),
+        Some(WordToken::Tilde(user)) if ends_word(user) && user.len() == word.len() => {
+            return (Context::User, &user[1..]);
+        },
+        // Expansions which have yet to be closed are given as they were written.
+        Some(WordToken::Normal(text)) if ends_word(text) => {
+            if text.starts_with("$(") || text.starts_with("@[") { return context(&text[2..]) }
+            if text.starts_with("${") && text[2..].chars().all(is_name_character) {
+                return (Context::Variable, &text[2..]);
+            }
+            // The quote which precedes the text has yet to be closed.
+            let offset = text.as_ptr() as usize - word.as_ptr() as usize;
+            if offset != 0 && word.as_bytes()[offset - 1] == b'\'' { return (Context::Literal, word) }
+        },
+        _ => ()
+    }
+
+    let token = if word.starts_with('"') { &word[1..] } else { word };
+    if expects_command(&words) {
+        (Context::Command, token)
+    } else if words[0] == "cd" || words[0] == "pushd" {
+        (Context::Directory, token)
+    } else {
+        (Context::Argument(words), token)
+    }
+}
+
+fn is_name_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+/// Whether the words form the beginning of a condition, within which `<` and `>` are comparisons.
+pub fn is_condition(words: &[String]) -> bool {
+    match words.first().map(|word| word.as_str()) {
+        Some("if") | Some("while") | Some("else") => true,
+        _ => false
+    }
+}
+
+/// Whether a command is expected to follow the words, which is the case at the beginning of a
+/// pipeline, and after the keywords which introduce a condition.
+pub fn expects_command(words: &[String]) -> bool {
+    if words.is_empty() { return true }
+    is_condition(words) && match words[words.len() - 1].as_str() {
+        "if" | "while" | "not" | "!" | "and" | "or" => true,
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argument(words: &[&str]) -> Context {
+        Context::Argument(words.iter().map(|&word| word.to_owned()).collect())
+    }
+
+    #[test]
+    fn completing_commands() {
+        assert_eq!(context(""), (Context::Command, ""));
+        assert_eq!(context("ca"), (Context::Command, "ca"));
+        assert_eq!(context("ls | gr"), (Context::Command, "gr"));
+        assert_eq!(context("ls|gr"), (Context::Command, "gr"));
+        assert_eq!(context("make && ./ta"), (Context::Command, "./ta"));
+        assert_eq!(context("false || ec"), (Context::Command, "ec"));
+        assert_eq!(context("cd src; ca"), (Context::Command, "ca"));
+        assert_eq!(context("cd src;"), (Context::Command, ""));
+        assert_eq!(context("sleep 1 & ec"), (Context::Command, "ec"));
+        assert_eq!(context("echo $(wh"), (Context::Command, "wh"));
+        assert_eq!(context("echo $(ls | gr"), (Context::Command, "gr"));
+        assert_eq!(context("if te"), (Context::Command, "te"));
+        assert_eq!(context("while not ex"), (Context::Command, "ex"));
+        assert_eq!(context("if test 1 and ex"), (Context::Command, "ex"));
+    }
+
+    #[test]
+    fn completing_arguments() {
+        assert_eq!(context("ls sr"), (argument(&["ls"]), "sr"));
+        assert_eq!(context("git commit -"), (argument(&["git", "commit"]), "-"));
+        assert_eq!(context("cat \"my fi"), (argument(&["cat"]), "my fi"));
+        assert_eq!(context("echo one\\ tw"), (argument(&["echo"]), "one\\ tw"));
+        assert_eq!(context("cat file | grep "), (argument(&["grep"]), ""));
+        assert_eq!(context("echo $(ls) "), (argument(&["echo", "$(ls)"]), ""));
+        assert_eq!(context("echo $(ls) sr"), (argument(&["echo", "$(ls)"]), "sr"));
+        assert_eq!(context("cd sr"), (Context::Directory, "sr"));
+        assert_eq!(context("pushd ../"), (Context::Directory, "../"));
+    }
+
+    #[test]
+    fn completing_redirections() {
+        assert_eq!(context("ls > ou"), (Context::Redirection, "ou"));
+        assert_eq!(context("ls >ou"), (Context::Redirection, "ou"));
+        assert_eq!(context("ls >> ou"), (Context::Redirection, "ou"));
+        assert_eq!(context("ls ^> er"), (Context::Redirection, "er"));
+        assert_eq!(context("ls &> ou"), (Context::Redirection, "ou"));
+        assert_eq!(context("cat < in"), (Context::Redirection, "in"));
+        assert_eq!(context("ls > "), (Context::Redirection, ""));
+        assert_eq!(context("if test $a > 5"), (argument(&["if", "test", "$a"]), "5"));
+    }
+
+    #[test]
+    fn completing_expansions() {
+        assert_eq!(context("echo $HO"), (Context::Variable, "HO"));
+        assert_eq!(context("echo ${HO"), (Context::Variable, "HO"));
+        assert_eq!(context("echo \"hello $US"), (Context::Variable, "US"));
+        assert_eq!(context("echo $"), (Context::Variable, ""));
+        assert_eq!(context("for x in @ar"), (Context::Array, "ar"));
+        assert_eq!(context("cd ~ro"), (Context::User, "ro"));
+        assert_eq!(context("cd ~/sr"), (Context::Directory, "~/sr"));
+        assert_eq!(context("echo 'hello $US"), (Context::Literal, "'hello $US"));
+        assert_eq!(context("echo $(echo ${HO"), (Context::Variable, "HO"));
+        assert_eq!(context("ls # sr"), (Context::Literal, ""));
+    }
+
+    #[test]
+    fn ranking_candidates() {
+        assert_eq!(rank("cargo", "car", Matching::Prefix), Some(0));
+        assert_eq!(rank("git-remote", "rem", Matching::Prefix), None);
+        assert_eq!(rank("git-remote", "rem", Matching::Substring), Some(1));
+        assert_eq!(rank("gitRemote", "remote", Matching::Substring), Some(1));
+        assert_eq!(rank("premote", "rem", Matching::Substring), Some(2));
+        assert_eq!(rank("premote", "pmt", Matching::Substring), None);
+        assert_eq!(rank("premote", "pmt", Matching::Fuzzy), Some(3));
+        assert_eq!(rank("premote", "tmp", Matching::Fuzzy), None);
+    }
+
+    #[test]
+    fn completing_ranked_words() {
+        let words = ["remote", "git-remote", "premote", "rmdir", "rename"].iter().map(|&w| w.to_owned()).collect();
+        let completer = RankedCompleter::new(vec![Source::Words(words)], Matching::Fuzzy);
+        assert_eq!(completer.completions("rem"), vec!["remote", "git-remote", "premote", "rename"]);
+
+        let words = vec!["remote".to_owned(), "premote".to_owned()];
+        let completer = RankedCompleter::new(vec![Source::Words(words)], Matching::Prefix);
+        assert_eq!(completer.completions("rem"), vec!["remote"]);
     }
 }
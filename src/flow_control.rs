@@ -1,11 +1,21 @@
+use glob::Pattern;
+use parser::conditionals::Conditional;
 use parser::peg::Pipeline;
+use parser::shell_expand::ranges::parse_range;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ElseIf {
-    pub expression: Pipeline,
+    pub expression: Conditional,
     pub success:    Vec<Statement>
 }
 
+/// An arm of a `match` statement. A `value` of `None` denotes the default `case _` arm.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Case {
+    pub value:      Option<String>,
+    pub statements: Vec<Statement>
+}
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum AssignmentOperator {
     Equal,
@@ -19,10 +29,11 @@ pub enum AssignmentOperator {
 pub enum Statement {
     Let {
         expression: String,
+        global: bool,
     },
     PrintVars,
     If {
-        expression: Pipeline,
+        expression: Conditional,
         success: Vec<Statement>,
         else_if: Vec<ElseIf>,
         failure: Vec<Statement>
@@ -31,6 +42,7 @@ pub enum Statement {
     Function {
         name: String,
         args: Vec<String>,
+        description: String,
         statements: Vec<Statement>
     },
     For {
@@ -39,96 +51,104 @@ pub enum Statement {
         statements: Vec<Statement>
     },
     While {
-        expression: Pipeline,
+        expression: Conditional,
         statements: Vec<Statement>
     },
+    Match {
+        expression: String,
+        cases: Vec<Case>
+    },
+    Case(Case),
     Else,
     End,
-    Break,
+    Break(usize),
+    Continue(usize),
     Pipeline(Pipeline),
     Default
 }
 
-pub struct FlowControl {
-    pub level:             usize,
-    pub current_statement: Statement,
-    pub current_if_mode:      u8 // { 0 = SUCCESS; 1 = FAILURE }
+/// Signals how execution should proceed after a block of statements has been executed.
+/// The `Break` and `Continue` variants store how many enclosing loops they apply to.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Condition {
+    NoOp,
+    Break(usize),
+    Continue(usize),
 }
 
-impl Default for FlowControl {
-    fn default() -> FlowControl {
-        FlowControl {
-            level:             0,
-            current_statement: Statement::Default,
-            current_if_mode:   0,
+impl Condition {
+    /// Returns the condition that a loop should pass up to its enclosing block after the
+    /// loop has been exited because of this condition.
+    pub fn unwind(self) -> Condition {
+        match self {
+            Condition::Break(levels) if levels > 1    => Condition::Break(levels - 1),
+            Condition::Continue(levels) if levels > 1 => Condition::Continue(levels - 1),
+            _ => Condition::NoOp,
         }
     }
 }
 
+/// Interactive input that has not yet been executed, because it contains blocks which have
+/// not yet been closed with an `end`.
+#[derive(Default)]
+pub struct FlowControl {
+    /// The number of blocks that are currently open within the buffer.
+    pub level:  usize,
+    /// Every line that has been supplied since the first block was opened.
+    pub buffer: String,
+}
+
 #[derive(Clone)]
 pub struct Function {
     pub name: String,
     pub args: Vec<String>,
+    pub description: String,
     pub statements: Vec<Statement>
 }
 
-pub fn collect_loops<I>(iterator: &mut I, statements: &mut Vec<Statement>, level: &mut usize)
-    where I: Iterator<Item = Statement>
-{
-    #[allow(while_let_on_iterator)]
-    while let Some(statement) = iterator.next() {
-        match statement {
-            Statement::While{..} | Statement::For{..} | Statement::If{..} |
-                Statement::Function{..} => *level += 1,
-            Statement::End if *level == 1 => { *level = 0; break },
-            Statement::End => *level -= 1,
-            _ => (),
-        }
-        statements.push(statement);
+/// Checks if an already-expanded case pattern matches the given value. Patterns may either be
+/// ranges, such as `1..10` or `a...f`, or glob patterns, which includes plain literals.
+pub fn pattern_matches(pattern: &str, value: &str) -> bool {
+    if let Some(matched) = range_matches(pattern, value) {
+        return matched;
+    }
+
+    match Pattern::new(pattern) {
+        Ok(glob) => glob.matches(value),
+        Err(_)   => pattern == value
     }
 }
 
-pub fn collect_if<I>(iterator: &mut I, success: &mut Vec<Statement>, else_if: &mut Vec<ElseIf>,
-    failure: &mut Vec<Statement>, level: &mut usize, mut current_block: u8)
-        -> Result<u8, &'static str>
-    where I: Iterator<Item = Statement>
-{
-    #[allow(while_let_on_iterator)]
-    while let Some(statement) = iterator.next() {
-        match statement {
-            Statement::While{..} | Statement::For{..} | Statement::If{..} |
-                Statement::Function{..} => *level += 1,
-            Statement::ElseIf(ref elseif) if *level == 1 => {
-                if current_block == 1 {
-                    return Err("ion: syntax error: else block already given");
-                } else {
-                    current_block = 2;
-                    else_if.push(elseif.clone());
-                    continue
-                }
-            }
-            Statement::Else if *level == 1 => {
-                current_block = 1;
-                continue
-            },
-            Statement::Else if *level == 1 && current_block == 1 => {
-                return Err("ion: syntax error: else block already given");
-            }
-            Statement::End if *level == 1 => { *level = 0; break },
-            Statement::End => *level -= 1,
-            _ => (),
-        }
+/// If the pattern is a range, returns whether the value is contained within that range.
+/// Like the ranges in `for` loops, `..` is exclusive and `...` is inclusive.
+fn range_matches(pattern: &str, value: &str) -> Option<bool> {
+    parse_range(pattern, false).map(|range| range.contains(value))
+}
 
-        match current_block {
-            0 => success.push(statement),
-            1 => failure.push(statement),
-            2 => {
-                let mut last = else_if.last_mut().unwrap(); // This is a bug if there isn't a value
-                last.success.push(statement);
-            }
-            _ => unreachable!()
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_case_patterns() {
+        assert!(pattern_matches("start", "start"));
+        assert!(!pattern_matches("start", "stop"));
+        assert!(pattern_matches("*.txt", "notes.txt"));
+        assert!(!pattern_matches("*.txt", "notes.rs"));
+        assert!(pattern_matches("file?", "file1"));
+        assert!(pattern_matches("1..5", "4"));
+        assert!(!pattern_matches("1..5", "5"));
+        assert!(pattern_matches("1...5", "5"));
+        assert!(pattern_matches("-5..0", "-3"));
+        assert!(!pattern_matches("1..5", "three"));
     }
 
-    Ok(current_block)
+    #[test]
+    fn unwinding_conditions() {
+        assert_eq!(Condition::NoOp, Condition::NoOp.unwind());
+        assert_eq!(Condition::NoOp, Condition::Break(1).unwind());
+        assert_eq!(Condition::Break(2), Condition::Break(3).unwind());
+        assert_eq!(Condition::NoOp, Condition::Continue(1).unwind());
+        assert_eq!(Condition::Continue(1), Condition::Continue(2).unwind());
+    }
 }
@@ -0,0 +1,360 @@
+//! Highlights the syntax of the command being typed, so that mistakes are seen before the command
+//! is executed.
+
+use std::str;
+
+use termion::style;
+use termion::color::{self, Fg};
+
+use completer::{expects_command, is_condition};
+use parser::{StatementError, StatementSplitter};
+use parser::pipelines;
+use parser::shell_expand::{WordIterator, WordToken};
+
+/// How a part of the command is displayed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Keyword,
+    Command,
+    UnknownCommand,
+    Quoted,
+    Variable,
+    Redirection,
+    Comment,
+    Error,
+}
+
+impl Style {
+    fn escape(self) -> String {
+        match self {
+            Style::Keyword        => format!("{}{}", style::Bold, Fg(color::Magenta)),
+            Style::Command        => format!("{}", Fg(color::Green)),
+            Style::UnknownCommand => format!("{}", Fg(color::Red)),
+            Style::Quoted         => format!("{}", Fg(color::Yellow)),
+            Style::Variable       => format!("{}", Fg(color::Cyan)),
+            Style::Redirection    => format!("{}", Fg(color::Blue)),
+            Style::Comment        => format!("{}", Fg(color::LightBlack)),
+            Style::Error          => format!("{}{}", style::Underline, Fg(color::Red)),
+        }
+    }
+}
+
+/// Determines the style of each byte of the line. The line is divided with the same tokenizers
+/// that the shell uses to execute it: statements by the `StatementSplitter`, words and
+/// redirections by `pipelines::collect`, and expansions by the `WordIterator`. Commands are
+/// checked with `is_command`, which is only given commands that contain no quotes or expansions.
+pub fn highlight<F>(line: &str, mut is_command: F) -> Vec<Option<Style>>
+    where F: FnMut(&str) -> bool
+{
+    let mut styles = vec![None; line.len()];
+    highlight_statements(line, line, &mut styles, &mut is_command);
+    styles
+}
+
+/// Highlights each statement of the text, which is either the line or a part of it, such as the
+/// command of a process expansion. Statements with syntax errors are only highlighted as errors.
+fn highlight_statements<F>(line: &str, text: &str, styles: &mut [Option<Style>], is_command: &mut F)
+    where F: FnMut(&str) -> bool
+{
+    let base = offset(line, text);
+    let mut splitter = StatementSplitter::new(text);
+    let mut statement_start = 0;
+    while let Some(statement) = splitter.next() {
+        match statement {
+            Ok(statement) => {
+                if !statement.is_empty() { highlight_pipeline(line, statement, styles, is_command); }
+                // A comment follows the statement when the statement was ended by a `#`.
+                let rest = &line[offset(line, statement) + statement.len()..base + text.len()];
+                if rest.trim_left().starts_with('#') {
+                    let comment = base + text.len() - rest.trim_left().len();
+                    paint(styles, comment, base + text.len(), Style::Comment);
+                }
+            },
+            Err(StatementError::InvalidCharacter(_, position)) => {
+                paint(styles, base + position - 1, base + position, Style::Error);
+            },
+            Err(_) => paint(styles, base + statement_start, base + splitter.offset(), Style::Error),
+        }
+        statement_start = splitter.offset();
+    }
+}
+
+/// Highlights the words of each job within the statement, and the operators between them.
+fn highlight_pipeline<F>(line: &str, statement: &str, styles: &mut [Option<Style>], is_command: &mut F)
+    where F: FnMut(&str) -> bool
+{
+    let base = offset(line, statement);
+    let mut error = None;
+    let pipeline = pipelines::collect(&mut error, statement);
+    // Within a condition, `<` and `>` are comparisons rather than redirections.
+    let condition = pipeline.jobs.first().map_or(false, |job| is_condition(&job.args[..1]));
+
+    // The words are found in the order that they were collected, and the files that the
+    // pipeline is redirected to or from follow the words of the final job.
+    let mut spans = Vec::new();
+    let mut read = 0;
+    for job in &pipeline.jobs {
+        for (index, arg) in job.args.iter().enumerate() {
+            let start = match locate(statement, arg, read) {
+                Some(start) => start,
+                None        => continue
+            };
+            read = start + arg.len();
+            spans.push((start, read));
+
+            let (start, end) = (base + start, base + read);
+            let preceding = &job.args[..index];
+            if is_keyword(arg, preceding) {
+                paint(styles, start, end, Style::Keyword);
+                continue
+            }
+            highlight_word(line, &line[start..end], styles, is_command);
+            if expects_command(preceding) && styles[start..end].iter().all(Option::is_none) {
+                let style = if is_command(&line[start..end]) { Style::Command } else { Style::UnknownCommand };
+                paint(styles, start, end, style);
+            }
+        }
+    }
+    for redirection in pipeline.stdin.iter().chain(pipeline.stdout.iter()) {
+        // The file is collected byte by byte, and may be cut apart from the text that it came from.
+        if str::from_utf8(redirection.file.as_bytes()).is_err() { continue }
+        if let Some(start) = locate(statement, &redirection.file, read) {
+            let end = start + redirection.file.len();
+            spans.push((start, end));
+            highlight_word(line, &line[base + start..base + end], styles, is_command);
+        }
+    }
+    spans.sort();
+
+    // Whatever lies between the words are the operators which separate the jobs and redirections.
+    let mut previous = 0;
+    for &(start, end) in spans.iter().chain(Some(&(statement.len(), statement.len()))) {
+        if start < previous { continue }
+        let mut operator = previous;
+        for (position, character) in statement[previous..start].char_indices().map(|(id, c)| (previous + id, c)) {
+            if character == ' ' || character == '\t' {
+                highlight_operator(&statement[operator..position], base + operator, condition, styles);
+                operator = position + 1;
+            }
+        }
+        highlight_operator(&statement[operator..start], base + operator, condition, styles);
+        previous = end;
+    }
+
+    if let Some((position, _)) = error {
+        let position = base + position;
+        paint(styles, position, position + 1, Style::Error);
+    }
+}
+
+/// Highlights pipes and redirections, but not the `&&`, `||`, and `&` which separate jobs.
+fn highlight_operator(operator: &str, start: usize, condition: bool, styles: &mut [Option<Style>]) {
+    let redirection = if operator.contains('>') || operator.contains('<') {
+        !condition
+    } else {
+        operator == "|" || operator == "^|"
+    };
+    if redirection { paint(styles, start, start + operator.len(), Style::Redirection); }
+}
+
+/// Highlights the quotes and expansions of the word, as the `WordIterator` finds them when the word
+/// is expanded. The commands of process expansions are highlighted as statements of their own.
+fn highlight_word<F>(line: &str, word: &str, styles: &mut [Option<Style>], is_command: &mut F)
+    where F: FnMut(&str) -> bool
+{
+    let end = offset(line, word) + word.len();
+    let mut read = offset(line, word);
+    // A word which leaves an expansion open, such as a job which was ended within braces,
+    // cannot be expanded.
+    if StatementSplitter::new(word).any(|statement| statement.is_err()) {
+        paint(styles, read, end, Style::Error);
+        return
+    }
+    // Where the quote which has yet to be closed begins.
+    let mut quote = None;
+    for token in WordIterator::new(word) {
+        let (start, finish) = token_span(line, &token);
+        // Only the quotes which the iterator removes from the word lie between its tokens.
+        toggle_quotes(line, read, start, &mut quote, styles);
+
+        match token {
+            WordToken::Variable(..) | WordToken::ArrayVariable(..) => {
+                paint(styles, start, finish, Style::Variable);
+            },
+            WordToken::Process(command, _) | WordToken::ArrayProcess(command, ..) => {
+                paint(styles, start, start + 2, Style::Variable);
+                paint(styles, finish - 1, finish, Style::Variable);
+                highlight_statements(line, command, styles, is_command);
+            },
+            WordToken::Brace(elements) => {
+                for element in elements { highlight_word(line, element, styles, is_command); }
+            },
+            _ => ()
+        }
+        if quote.is_some() { fill(styles, start, finish, Style::Quoted); }
+        read = finish;
+    }
+    toggle_quotes(line, read, end, &mut quote, styles);
+
+    if let Some(quote) = quote {
+        paint(styles, quote, end, Style::Error);
+    }
+}
+
+/// Paints the quotes between `start` and `end`, each of which opens or closes a quote.
+fn toggle_quotes(line: &str, start: usize, end: usize, quote: &mut Option<usize>, styles: &mut [Option<Style>]) {
+    for (position, &byte) in line.as_bytes()[start..end].iter().enumerate().map(|(id, byte)| (start + id, byte)) {
+        if byte == b'\'' || byte == b'"' {
+            *quote = if quote.is_some() { None } else { Some(position) };
+            paint(styles, position, position + 1, Style::Quoted);
+        }
+    }
+}
+
+/// The offsets within the line of the text that the token was read from, including the
+/// characters which introduce and end an expansion, such as `${` and `}`.
+fn token_span(line: &str, token: &WordToken) -> (usize, usize) {
+    match *token {
+        WordToken::Normal(text) | WordToken::Whitespace(text) | WordToken::Tilde(text) => {
+            let start = offset(line, text);
+            (start, start + text.len())
+        },
+        WordToken::Variable(name, _) | WordToken::ArrayVariable(name, ..) => {
+            let start = offset(line, name);
+            if line.as_bytes()[start - 1] == b'{' { (start - 2, start + name.len() + 1) } else { (start - 1, start + name.len()) }
+        },
+        WordToken::Process(command, _) | WordToken::ArrayProcess(command, ..) => {
+            let start = offset(line, command);
+            (start - 2, start + command.len() + 1)
+        },
+        WordToken::Brace(ref elements) => {
+            let start = elements.first().map_or(0, |element| offset(line, element));
+            let end = elements.last().map_or(0, |element| offset(line, element) + element.len());
+            (start - 1, end + 1)
+        }
+    }
+}
+
+/// The offset within the line of a part of it.
+fn offset(line: &str, part: &str) -> usize {
+    part.as_ptr() as usize - line.as_ptr() as usize
+}
+
+/// Finds the word within the text, at or after `from`, where it is not a part of a longer word.
+fn locate(text: &str, word: &str, from: usize) -> Option<usize> {
+    let separates = |byte: Option<&u8>| byte.map_or(true, |byte| b" \t|&<>^".contains(byte));
+    let mut from = from;
+    while let Some(position) = text[from..].find(word) {
+        let start = from + position;
+        let end = start + word.len();
+        if (start == 0 || separates(text.as_bytes().get(start - 1))) && separates(text.as_bytes().get(end)) {
+            return Some(start);
+        }
+        from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+/// Whether the word is a keyword, given the words of the command which precede it.
+fn is_keyword(word: &str, words: &[String]) -> bool {
+    if expects_command(words) {
+        match word {
+            "if" | "else" | "end" | "for" | "while" | "match" | "case" | "fn" | "let" | "export" | "break"
+                | "continue" | "not" => true,
+            _ => false
+        }
+    } else if words.len() == 1 && words[0] == "else" {
+        word == "if"
+    } else if words.len() == 2 && words[0] == "for" {
+        word == "in"
+    } else {
+        is_condition(words) && (word == "and" || word == "or")
+    }
+}
+
+fn paint(styles: &mut [Option<Style>], start: usize, end: usize, style: Style) {
+    for byte in &mut styles[start..end] {
+        *byte = Some(style);
+    }
+}
+
+/// Paints only the bytes which do not already have a style, such as those around variables.
+fn fill(styles: &mut [Option<Style>], start: usize, end: usize, style: Style) {
+    for byte in styles[start..end].iter_mut().filter(|byte| byte.is_none()) {
+        *byte = Some(style);
+    }
+}
+
+/// Renders the line with the escape sequences of each style.
+pub fn render(line: &str, styles: &[Option<Style>]) -> String {
+    let mut output = String::with_capacity(line.len() * 2);
+    let mut current = None;
+    for (id, character) in line.char_indices() {
+        if styles[id] != current {
+            output.push_str(&style::Reset.to_string());
+            if let Some(style) = styles[id] { output.push_str(&style.escape()); }
+            current = styles[id];
+        }
+        output.push(character);
+    }
+    if current.is_some() { output.push_str(&style::Reset.to_string()); }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Groups the bytes of the line which share a style.
+    fn spans(line: &str) -> Vec<(&str, Style)> {
+        let styles = highlight(line, |command| command == "echo" || command == "cat");
+        let mut spans = Vec::new();
+        let mut start = 0;
+        for end in 1..line.len() + 1 {
+            if end == line.len() || styles[end] != styles[start] {
+                if let Some(style) = styles[start] { spans.push((&line[start..end], style)); }
+                start = end;
+            }
+        }
+        spans
+    }
+
+    #[test]
+    fn highlighting_commands() {
+        assert_eq!(spans("echo one | grep two"), vec![
+            ("echo", Style::Command), ("|", Style::Redirection), ("grep", Style::UnknownCommand)
+        ]);
+        assert_eq!(spans("if not cat file && ech; echo"), vec![
+            ("if", Style::Keyword), ("not", Style::Keyword), ("cat", Style::Command),
+            ("ech", Style::UnknownCommand), ("echo", Style::Command)
+        ]);
+        assert_eq!(spans("for x in 1..3"), vec![("for", Style::Keyword), ("in", Style::Keyword)]);
+        assert_eq!(spans("let a = b # echo"), vec![("let", Style::Keyword), ("# echo", Style::Comment)]);
+    }
+
+    #[test]
+    fn highlighting_arguments() {
+        assert_eq!(spans("echo 'a $b' \"c $d\""), vec![
+            ("echo", Style::Command), ("'a $b'", Style::Quoted), ("\"c ", Style::Quoted),
+            ("$d", Style::Variable), ("\"", Style::Quoted)
+        ]);
+        assert_eq!(spans("echo $(cat @x) ~/$y"), vec![
+            ("echo", Style::Command), ("$(", Style::Variable), ("cat", Style::Command),
+            ("@x)", Style::Variable), ("$y", Style::Variable)
+        ]);
+        assert_eq!(spans("echo {a,$b} ^>> log"), vec![
+            ("echo", Style::Command), ("$b", Style::Variable), ("^>>", Style::Redirection)
+        ]);
+        assert_eq!(spans("cat < file > out"), vec![
+            ("cat", Style::Command), ("<", Style::Redirection), (">", Style::Redirection)
+        ]);
+    }
+
+    #[test]
+    fn highlighting_errors() {
+        assert_eq!(spans("echo \"one two"), vec![("echo", Style::Command), ("\"one two", Style::Error)]);
+        assert_eq!(spans("echo (one)"), vec![("(", Style::Error)]);
+        assert_eq!(spans("echo ${one"), vec![("echo ${one", Style::Error)]);
+        assert_eq!(spans("cat file >"), vec![("cat", Style::Command), (">", Style::Error)]);
+    }
+}
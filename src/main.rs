@@ -5,24 +5,31 @@
 #![plugin(peg_syntax_ext)]
 extern crate glob;
 extern crate liner;
+extern crate termion;
 
+#[cfg(all(unix, not(target_os = "redox")))]
+extern crate libc;
 #[cfg(all(unix, not(target_os = "redox")))]
 extern crate users as users_unix;
 
 pub mod completer;
+pub mod highlighter;
 pub mod pipe;
 pub mod directory_stack;
 pub mod to_num;
 pub mod variables;
 pub mod status;
 pub mod flow_control;
+pub mod sys;
 mod builtins;
 mod parser;
 mod shell;
+#[cfg(test)]
+mod test_helpers;
 
 use std::io::{stderr, Write, ErrorKind};
 use builtins::Builtin;
-use shell::Shell;
+use shell::{Shell, ShellHistory};
 
 fn main() {
     let builtins = Builtin::map();
@@ -30,7 +37,7 @@ fn main() {
     shell.evaluate_init_file();
 
     if "1" == shell.variables.get_var_or_empty("HISTORY_FILE_ENABLED") {
-        match shell.context.history.load_history() {
+        match shell.load_history() {
             Ok(()) => {
                 // pass
             }
@@ -68,3 +68,28 @@ impl<'a> Iterator for ArgumentSplitter<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(data: &str) -> Vec<String> {
+        ArgumentSplitter::new(data).collect()
+    }
+
+    #[test]
+    fn split_array_processes() {
+        assert_eq!(split("@[echo one two] three"), vec!["@[echo one two]", "three"]);
+    }
+
+    #[test]
+    fn split_quoted_arguments() {
+        assert_eq!(split("'one two' \"three four\" five"), vec!["'one two'", "\"three four\"", "five"]);
+        assert_eq!(split("'$(one two)' \"@[three four]\""), vec!["'$(one two)'", "\"@[three four]\""]);
+    }
+
+    #[test]
+    fn split_escaped_spaces() {
+        assert_eq!(split("one\\ two three"), vec!["one\\ two", "three"]);
+    }
+}
@@ -0,0 +1,323 @@
+use std::cmp::Ordering;
+
+use glob::Pattern;
+use parser::pipelines;
+use parser::peg::Pipeline;
+
+/// The operators that may be used to compare two values within a conditional expression.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Comparator {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Matches,
+}
+
+impl Comparator {
+    fn from_token(token: &str) -> Option<Comparator> {
+        match token {
+            "==" => Some(Comparator::Equal),
+            "!=" => Some(Comparator::NotEqual),
+            "<"  => Some(Comparator::Less),
+            "<=" => Some(Comparator::LessEqual),
+            ">"  => Some(Comparator::Greater),
+            ">=" => Some(Comparator::GreaterEqual),
+            "=~" => Some(Comparator::Matches),
+            _    => None
+        }
+    }
+
+    /// Compares two expanded values. Equality is a string comparison, whereas ordering
+    /// comparisons are numeric if both values are numbers, and lexicographic otherwise.
+    /// The `=~` operator checks if the left value matches the glob pattern on the right.
+    pub fn compare(self, left: &str, right: &str) -> bool {
+        if self == Comparator::Equal { return left == right }
+        if self == Comparator::NotEqual { return left != right }
+        if self == Comparator::Matches {
+            return Pattern::new(right).map(|pattern| pattern.matches(left)).unwrap_or(false)
+        }
+
+        let ordering = match (left.parse::<f64>(), right.parse::<f64>()) {
+            (Ok(left), Ok(right)) => match left.partial_cmp(&right) {
+                Some(ordering) => ordering,
+                None           => return false
+            },
+            _ => left.cmp(right)
+        };
+
+        match self {
+            Comparator::Less         => ordering == Ordering::Less,
+            Comparator::LessEqual    => ordering != Ordering::Greater,
+            Comparator::Greater      => ordering == Ordering::Greater,
+            Comparator::GreaterEqual => ordering != Ordering::Less,
+            _                        => unreachable!()
+        }
+    }
+}
+
+/// The expression evaluated by `if`, `else if`, and `while` statements.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Conditional {
+    /// Succeeds if the pipeline exits with a successful status.
+    Pipeline(Pipeline),
+    /// Compares two values in the shell, without spawning a process.
+    Comparison(String, Comparator, String),
+    Not(Box<Conditional>),
+    And(Box<Conditional>, Box<Conditional>),
+    Or(Box<Conditional>, Box<Conditional>),
+}
+
+/// Parses a conditional expression, such as `$a == 5 && not exists -f file`.
+///
+/// The `not` and `!` operators have the highest precedence, followed by `and` / `&&`, and
+/// lastly `or` / `||`. Each operand is either a comparison between two values, or a pipeline.
+/// As the words of a pipeline may be any argument, a pipeline is only ended by `&&` or `||`,
+/// whereas `and` and `or` may only follow a comparison, and `not` and `!` may only begin an operand.
+/// An error is given along with the offset within the expression of the token which caused it.
+pub fn parse(expression: &str) -> Result<Conditional, (usize, &'static str)> {
+    let tokens = words(expression);
+    let mut parser = ConditionalParser { expression: expression, tokens: &tokens, read: 0 };
+    parser.or()
+}
+
+/// Splits the expression into words, along with the offset of each word within the expression.
+/// Quoted strings, and the processes and arrays given by `$(..)`, `@(..)`, `$[..]`, and `@[..]`,
+/// are kept within a single word, however deeply their parentheses and brackets are nested.
+fn words(expression: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let (mut level, mut single, mut double, mut escaped, mut sigil) = (0, false, false, false, false);
+    for (index, character) in expression.char_indices() {
+        let opens = sigil || level != 0;
+        sigil = false;
+        if start.is_none() {
+            if character.is_whitespace() { continue }
+            start = Some(index);
+        }
+        if escaped {
+            escaped = false;
+            continue
+        }
+
+        match character {
+            '\\' if !single => escaped = true,
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            _ if single => (),
+            '$' | '@' => sigil = true,
+            '(' | '[' if opens => level += 1,
+            ')' | ']' if level != 0 => level -= 1,
+            _ if character.is_whitespace() && !double && level == 0 => {
+                let begin = start.take().unwrap();
+                words.push((begin, &expression[begin..index]));
+            },
+            _ => ()
+        }
+    }
+
+    if let Some(begin) = start {
+        words.push((begin, &expression[begin..]));
+    }
+    words
+}
+
+fn is_junction(token: &str) -> bool {
+    match token {
+        "and" | "&&" | "or" | "||" => true,
+        _ => false
+    }
+}
+
+/// Determines if a token is clearly a value rather than the name of a command, which is used
+/// to distinguish comparisons from commands, IE: `$a > 5` versus `cat > file`, or `$a == b`
+/// versus `echo == b`.
+fn is_operand(token: &str) -> bool {
+    token.starts_with(|c| c == '$' || c == '@' || c == '"' || c == '\'')
+        || token.parse::<f64>().is_ok()
+}
+
+struct ConditionalParser<'a> {
+    expression: &'a str,
+    tokens:     &'a [(usize, &'a str)],
+    read:       usize,
+}
+
+impl<'a> ConditionalParser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.read).map(|&(_, token)| token)
+    }
+
+    fn or(&mut self) -> Result<Conditional, (usize, &'static str)> {
+        let mut left = self.and()?;
+        while let Some(token) = self.peek() {
+            if token != "or" && token != "||" { break }
+            self.read += 1;
+            let right = self.and()?;
+            left = Conditional::Or(box left, box right);
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Conditional, (usize, &'static str)> {
+        let mut left = self.not()?;
+        while let Some(token) = self.peek() {
+            if token != "and" && token != "&&" { break }
+            self.read += 1;
+            let right = self.not()?;
+            left = Conditional::And(box left, box right);
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Conditional, (usize, &'static str)> {
+        match self.peek() {
+            Some("not") | Some("!") => {
+                self.read += 1;
+                Ok(Conditional::Not(box self.not()?))
+            },
+            _ => self.operand()
+        }
+    }
+
+    fn operand(&mut self) -> Result<Conditional, (usize, &'static str)> {
+        if let Some(comparison) = self.comparison() {
+            return Ok(comparison);
+        }
+
+        let start = self.read;
+        while let Some(token) = self.peek() {
+            if token == "&&" || token == "||" { break }
+            self.read += 1;
+        }
+        if start == self.read {
+            let offset = self.tokens.get(self.read).map_or(self.expression.len(), |&(offset, _)| offset);
+            return Err((offset, "expected a condition"));
+        }
+
+        // The pipeline is collected from the expression as it was written, rather than from its words.
+        let (first, _) = self.tokens[start];
+        let (last, token) = self.tokens[self.read - 1];
+        let mut possible_error = None;
+        let pipeline = pipelines::collect(&mut possible_error, &self.expression[first..last + token.len()]);
+        match possible_error {
+            Some((offset, error)) => Err((first + offset, error)),
+            None                  => Ok(Conditional::Pipeline(pipeline))
+        }
+    }
+
+    /// Reads a comparison, which consists of exactly three words, if one is next.
+    fn comparison(&mut self) -> Option<Conditional> {
+        let tokens = self.tokens;
+        let tokens = &tokens[self.read..];
+        if tokens.len() < 3 || tokens.get(3).map_or(false, |&(_, token)| !is_junction(token)) {
+            return None;
+        }
+
+        let (left, right) = (tokens[0].1, tokens[2].1);
+        if !is_operand(left) { return None }
+        let comparator = match Comparator::from_token(tokens[1].1) {
+            Some(comparator) => comparator,
+            None => return None
+        };
+        self.read += 3;
+        Some(Conditional::Comparison(left.to_owned(), comparator, right.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_helpers::pipeline;
+
+    #[test]
+    fn parse_comparisons() {
+        assert_eq!(Ok(Conditional::Comparison("$a".into(), Comparator::Equal, "5".into())), parse("$a == 5"));
+        assert_eq!(Ok(Conditional::Comparison("$a".into(), Comparator::Greater, "5".into())), parse("$a > 5"));
+        assert_eq!(
+            Ok(Conditional::Comparison("\"$a b\"".into(), Comparator::NotEqual, "c".into())),
+            parse("\"$a b\" != c")
+        );
+    }
+
+    #[test]
+    fn parse_commands_are_not_comparisons() {
+        assert_eq!(Ok(Conditional::Pipeline(pipeline(&["echo", "==", "foo"]))), parse("echo == foo"));
+        assert_eq!(Ok(Conditional::Pipeline(pipeline(&["test", "=~", "x"]))), parse("test =~ x"));
+    }
+
+    #[test]
+    fn parse_redirection_is_not_a_comparison() {
+        if let Ok(Conditional::Pipeline(pipeline)) = parse("cat > file") {
+            assert_eq!("file", pipeline.stdout.unwrap().file);
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn parse_junctions() {
+        let expected = Conditional::Or(
+            box Conditional::And(
+                box Conditional::Pipeline(pipeline(&["true"])),
+                box Conditional::Not(box Conditional::Pipeline(pipeline(&["false"])))
+            ),
+            box Conditional::Comparison("$a".into(), Comparator::LessEqual, "1".into())
+        );
+        assert_eq!(Ok(expected), parse("true && ! false || $a <= 1"));
+
+        let expected = Conditional::Or(
+            box Conditional::And(
+                box Conditional::Comparison("$a".into(), Comparator::Equal, "1".into()),
+                box Conditional::Not(box Conditional::Comparison("$b".into(), Comparator::Matches, "*.rs".into()))
+            ),
+            box Conditional::Pipeline(pipeline(&["true"]))
+        );
+        assert_eq!(Ok(expected), parse("$a == 1 and not $b =~ *.rs or true"));
+    }
+
+    #[test]
+    fn parse_junction_words_as_arguments() {
+        assert_eq!(Ok(Conditional::Pipeline(pipeline(&["grep", "-q", "or", "file"]))), parse("grep -q or file"));
+        assert_eq!(
+            Ok(Conditional::And(
+                box Conditional::Pipeline(pipeline(&["echo", "not", "and"])),
+                box Conditional::Pipeline(pipeline(&["true"]))
+            )),
+            parse("echo not and && true")
+        );
+    }
+
+    #[test]
+    fn parse_nested_processes() {
+        assert_eq!(
+            Ok(Conditional::Comparison("$(echo $(echo a b))".into(), Comparator::Equal, "\"a b\"".into())),
+            parse("$(echo $(echo a b)) == \"a b\"")
+        );
+        assert_eq!(
+            Ok(Conditional::Comparison("@[echo a (b)]".into(), Comparator::NotEqual, "'c d'".into())),
+            parse("@[echo a (b)] != 'c d'")
+        );
+    }
+
+    #[test]
+    fn parse_missing_condition() {
+        assert_eq!(parse(""), Err((0, "expected a condition")));
+        assert_eq!(parse("not"), Err((3, "expected a condition")));
+        assert_eq!(parse("true && || false"), Err((8, "expected a condition")));
+        assert_eq!(parse("$a == 1 && cat >"), Err((15, "missing standard output file argument after '>'")));
+    }
+
+    #[test]
+    fn compare_values() {
+        assert!(Comparator::Equal.compare("a", "a"));
+        assert!(Comparator::NotEqual.compare("a", "b"));
+        assert!(Comparator::Less.compare("9", "10"));
+        assert!(Comparator::Greater.compare("b", "a"));
+        assert!(Comparator::GreaterEqual.compare("5", "5.0"));
+        assert!(Comparator::Matches.compare("main.rs", "*.rs"));
+        assert!(!Comparator::Matches.compare("main.c", "*.rs"));
+    }
+}
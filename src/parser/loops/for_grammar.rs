@@ -1,72 +1,120 @@
 use directory_stack::DirectoryStack;
 use variables::Variables;
-use parser::expand_string;
+use parser::{expand_command, expand_defined, expand_string};
+use parser::shell_expand::ranges::{parse_range, Range};
+
+/// Where the records of a `ForExpression::Stream` will be read from.
+#[derive(Debug, PartialEq)]
+pub enum StreamSource {
+    /// The path of a file, given as `for line in < file`
+    File(String),
+    /// A command whose standard output will be read, given as `for line in | command`
+    Command(String),
+}
 
 #[derive(Debug, PartialEq)]
 pub enum ForExpression {
     Multiple(Vec<String>),
     Normal(String),
-    Range(usize, usize)
+    Range(Range),
+    /// Records that are lazily read from a source, delimited by the given byte.
+    Stream(StreamSource, u8)
 }
 
 impl ForExpression {
-    pub fn new(expression: &[String], dir_stack: &DirectoryStack, variables: &Variables) -> ForExpression {
-        let mut output: Vec<String> = expression.iter()
-            .flat_map(|expression| expand_string(expression, variables, dir_stack, true))
-            .collect();
+    /// Expands the values of a `for` loop. If `nounset` is set, the name of the first undefined
+    /// variable which the values reference is returned as an error.
+    pub fn new(expression: &[String], dir_stack: &DirectoryStack, variables: &Variables, nounset: bool)
+        -> Result<ForExpression, String>
+    {
+        let expand = |expression: &str, reverse_quoting: bool| if nounset {
+            expand_defined(expression, variables, dir_stack, reverse_quoting)
+        } else {
+            Ok(expand_string(expression, variables, dir_stack, reverse_quoting))
+        };
+
+        // Streams are read lazily, one record at a time, rather than being expanded upfront.
+        // Preceding the stream with `-0` will delimit records by NUL bytes instead of newlines.
+        let (delimiter, stream) = match expression.first().map(String::as_str) {
+            Some("-0") => (b'\0', &expression[1..]),
+            _          => (b'\n', expression)
+        };
+
+        match stream.first().map(String::as_str) {
+            Some("<") => {
+                let mut path = Vec::new();
+                for word in &stream[1..] { path.extend(expand(word.as_str(), false)?); }
+                return Ok(ForExpression::Stream(StreamSource::File(path.join(" ")), delimiter));
+            },
+            Some("|") => {
+                let command = expand_command(&stream[1..].join(" "), variables, dir_stack);
+                return Ok(ForExpression::Stream(StreamSource::Command(command), delimiter));
+            },
+            _ => ()
+        }
+
+        let mut output: Vec<String> = Vec::new();
+        for word in expression { output.extend(expand(word.as_str(), true)?); }
 
         if output.len() == 1 {
             let output = output.drain(..).next().unwrap();
-            {
-                let mut bytes_iterator = output.bytes().enumerate();
-                while let Some((id, byte)) = bytes_iterator.next() {
-                    match byte {
-                        b'0'...b'9' => continue,
-                        b'.' => match output[0..id].parse::<usize>().ok() {
-                            Some(first_number) => {
-                                let mut dots = 1;
-                                for (_, byte) in bytes_iterator {
-                                    if byte == b'.' { dots += 1 } else { break }
-                                }
-
-                                match output[id+dots..].parse::<usize>().ok() {
-                                    Some(second_number) => {
-                                        match dots {
-                                            2 => return ForExpression::Range(first_number, second_number),
-                                            3 => return ForExpression::Range(first_number, second_number+1),
-                                            _ => break
-                                        }
-                                    },
-                                    None => break
-                                }
-                            },
-                            None => break
-                        },
-                        _ => break
-                    }
-                }
+            match parse_range(&output, false) {
+                Some(range) => Ok(ForExpression::Range(range)),
+                None        => Ok(ForExpression::Normal(output))
             }
-            ForExpression::Normal(output)
         } else {
-            ForExpression::Multiple(output)
+            Ok(ForExpression::Multiple(output))
         }
     }
 }
 
-#[test]
-fn for_inclusive_range() {
+#[cfg(test)]
+fn range_values(input: &str) -> Vec<String> {
     let dir_stack = DirectoryStack::new().unwrap();
     let variables = Variables::default();
-    let input = &["1...10".to_owned()];
-    assert_eq!(ForExpression::new(input, &dir_stack, &variables), ForExpression::Range(1, 11));
+    match ForExpression::new(&[input.to_owned()], &dir_stack, &variables, false).unwrap() {
+        ForExpression::Range(range) => range.collect(),
+        expression => panic!("{} was not parsed as a range: {:?}", input, expression)
+    }
+}
+
+#[test]
+fn for_inclusive_range() {
+    assert_eq!(range_values("1...5"), vec!["1", "2", "3", "4", "5"]);
 }
 
 #[test]
 fn for_exclusive_range() {
+    assert_eq!(range_values("1..5"), vec!["1", "2", "3", "4"]);
+}
+
+#[test]
+fn for_descending_range() {
+    assert_eq!(range_values("10..5"), vec!["10", "9", "8", "7", "6"]);
+    assert_eq!(range_values("2...-2"), vec!["2", "1", "0", "-1", "-2"]);
+}
+
+#[test]
+fn for_stepped_range() {
+    assert_eq!(range_values("0..20..5"), vec!["0", "5", "10", "15"]);
+    assert_eq!(range_values("20...0..10"), vec!["20", "10", "0"]);
+}
+
+#[test]
+fn for_character_range() {
+    assert_eq!(range_values("a...e"), vec!["a", "b", "c", "d", "e"]);
+    assert_eq!(range_values("Z..W"), vec!["Z", "Y", "X"]);
+}
+
+#[test]
+fn for_variable_range() {
     let dir_stack = DirectoryStack::new().unwrap();
-    let variables = Variables::default();
-    let input = &["1..10".to_owned()];
-    assert_eq!(ForExpression::new(input, &dir_stack, &variables), ForExpression::Range(1, 10));
+    let mut variables = Variables::default();
+    variables.set_var("END", "3");
+    match ForExpression::new(&["1...$END".to_owned()], &dir_stack, &variables, false).unwrap() {
+        ForExpression::Range(range) => assert_eq!(range.collect::<Vec<String>>(), vec!["1", "2", "3"]),
+        expression => panic!("1...$END was not parsed as a range: {:?}", expression)
+    }
 }
 
 #[test]
@@ -74,7 +122,8 @@ fn for_normal() {
     let dir_stack = DirectoryStack::new().unwrap();
     let variables = Variables::default();
     let output = vec!["1".to_owned(), "2".to_owned(), "3".to_owned(), "4".to_owned(), "5".to_owned()];
-    assert_eq!(ForExpression::new(&output.clone(), &dir_stack, &variables), ForExpression::Multiple(output));
+    assert_eq!(ForExpression::new(&output.clone(), &dir_stack, &variables, false).unwrap(),
+        ForExpression::Multiple(output));
 }
 
 #[test]
@@ -82,6 +131,28 @@ fn for_variable() {
     let dir_stack = DirectoryStack::new().unwrap();
     let mut variables = Variables::default();
     variables.set_var("A", "1 2 3 4 5");
-    assert_eq!(ForExpression::new(&["$A".to_owned()], &dir_stack, &variables),
+    assert_eq!(ForExpression::new(&["$A".to_owned()], &dir_stack, &variables, false).unwrap(),
         ForExpression::Normal("1 2 3 4 5".to_owned()));
 }
+
+#[test]
+fn for_streams() {
+    let dir_stack = DirectoryStack::new().unwrap();
+    let mut variables = Variables::default();
+    variables.set_var("FILE", "access.log");
+    assert_eq!(ForExpression::new(&["<".to_owned(), "$FILE".to_owned()], &dir_stack, &variables, false).unwrap(),
+        ForExpression::Stream(StreamSource::File("access.log".to_owned()), b'\n'));
+
+    let input = &["-0".to_owned(), "|".to_owned(), "find".to_owned(), "$FILE".to_owned(), "'-print0'".to_owned()];
+    assert_eq!(ForExpression::new(input, &dir_stack, &variables, false).unwrap(),
+        ForExpression::Stream(StreamSource::Command("find access.log '-print0'".to_owned()), b'\0'));
+}
+
+#[test]
+fn for_undefined_variable() {
+    let dir_stack = DirectoryStack::new().unwrap();
+    let variables = Variables::default();
+    let values = &["1".to_owned(), "$UNDEFINED".to_owned()];
+    assert_eq!(ForExpression::new(values, &dir_stack, &variables, true), Err("UNDEFINED".to_owned()));
+    assert_eq!(ForExpression::new(&values[..1], &dir_stack, &variables, true), Ok(ForExpression::Normal("1".to_owned())));
+}
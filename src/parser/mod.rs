@@ -1,24 +1,32 @@
+use std::cell::RefCell;
+
 use variables::Variables;
 use directory_stack::DirectoryStack;
 
 mod arguments;
 pub mod assignments;
+pub mod conditionals;
 mod loops;
 pub mod peg;
 pub mod pipelines;
 pub mod shell_expand;
 mod statements;
 mod quotes;
+mod script;
 
 pub use self::shell_expand::{Index, ExpanderFunctions};
 pub use self::arguments::ArgumentSplitter;
-pub use self::loops::for_grammar::ForExpression;
+pub use self::loops::for_grammar::{ForExpression, StreamSource};
 pub use self::statements::{StatementSplitter, StatementError, check_statement};
 pub use self::quotes::QuoteTerminator;
+pub use self::script::{parse_script, parse_complete_script, incomplete_depth, ErrorKind, SyntaxError};
 
-/// Takes an argument string as input and expands it.
-pub fn expand_string<'a>(original: &'a str, vars: &Variables, dir_stack: &DirectoryStack,
-    reverse_quoting: bool) -> Vec<String>
+/// Constructs the expansion functions for the given variables and directory stack, and supplies
+/// them to the given closure. The name of the first variable which could not be found is
+/// recorded within `undefined`.
+pub fn with_expanders<T, F>(vars: &Variables, dir_stack: &DirectoryStack, undefined: &RefCell<Option<String>>,
+    action: F) -> T
+    where F: FnOnce(&ExpanderFunctions) -> T
 {
     let expanders = ExpanderFunctions {
         tilde: &|tilde: &str| vars.tilde_expansion(tilde, dir_stack),
@@ -31,9 +39,42 @@ pub fn expand_string<'a>(original: &'a str, vars: &Variables, dir_stack: &Direct
             }
         },
         variable: &|variable: &str, quoted: bool| {
-            if quoted { vars.get_var(variable) } else { vars.get_var(variable).map(|x| x.replace("\n", " ")) }
+            let value = vars.get_var(variable);
+            if value.is_none() {
+                let mut undefined = undefined.borrow_mut();
+                if undefined.is_none() { *undefined = Some(variable.to_owned()); }
+            }
+            if quoted { value } else { value.map(|x| x.replace("\n", " ")) }
         },
         command: &|command: &str, quoted: bool| vars.command_expansion(command, quoted),
     };
-    shell_expand::expand_string(original, &expanders, reverse_quoting)
+    action(&expanders)
+}
+
+/// Takes an argument string as input and expands it.
+pub fn expand_string<'a>(original: &'a str, vars: &Variables, dir_stack: &DirectoryStack,
+    reverse_quoting: bool) -> Vec<String>
+{
+    with_expanders(vars, dir_stack, &RefCell::new(None),
+        |expanders| shell_expand::expand_string(original, expanders, reverse_quoting))
+}
+
+/// Expands the argument in the same manner as `expand_string`, but fails with the name of the
+/// first variable referenced by the argument which has not been defined.
+pub fn expand_defined(original: &str, vars: &Variables, dir_stack: &DirectoryStack,
+    reverse_quoting: bool) -> Result<Vec<String>, String>
+{
+    let undefined = RefCell::new(None);
+    let expanded = with_expanders(vars, dir_stack, &undefined,
+        |expanders| shell_expand::expand_string(original, expanders, reverse_quoting));
+    match undefined.into_inner() {
+        Some(variable) => Err(variable),
+        None           => Ok(expanded)
+    }
+}
+
+/// Expands the variables within a command that will be executed by a subshell.
+pub fn expand_command(command: &str, vars: &Variables, dir_stack: &DirectoryStack) -> String {
+    with_expanders(vars, dir_stack, &RefCell::new(None),
+        |expanders| shell_expand::expand_command(command, true, expanders))
 }
@@ -1,9 +1,11 @@
+use std::fmt;
 use std::io::{stderr, Write};
 
 use flow_control::Statement;
 use self::grammar::parse_;
+pub use self::grammar::ParseError;
 use directory_stack::DirectoryStack;
-use shell::Job;
+use shell::{Job, JobKind};
 use variables::Variables;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -33,15 +35,86 @@ impl Pipeline {
         }
     }
 
-    pub fn expand(&mut self, variables: &Variables, dir_stack: &DirectoryStack) {
+    /// Expands the arguments of each job. If `nounset` is set, the name of the first undefined
+    /// variable is returned as an error.
+    pub fn expand(&mut self, variables: &Variables, dir_stack: &DirectoryStack, nounset: bool)
+        -> Result<(), String>
+    {
         for job in &mut self.jobs {
-            job.expand(variables, dir_stack);
+            job.expand(variables, dir_stack, nounset)?;
         }
+        Ok(())
     }
 }
 
-pub fn parse(code: &str) -> Statement {
+/// Quotes the argument if it is empty, or contains whitespace or characters which are special to
+/// the shell, so that it would be read back as the same argument. As nothing is escaped within
+/// single quotes, each single quote is written as `'"'"'`, which ends the quotes, gives the single
+/// quote within double quotes, and begins the quotes again.
+fn quote(arg: &str) -> String {
+    const SPECIAL: &'static str = "'\"\\$@|&;<>(){}[]*?#~^!";
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || SPECIAL.contains(c)) {
+        return arg.to_owned();
+    }
+    format!("'{}'", arg.replace('\'', "'\"'\"'"))
+}
+
+impl fmt::Display for Pipeline {
+    /// Formats the pipeline as it would be written, quoting any arguments that need to be quoted.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for job in &self.jobs {
+            for (id, arg) in job.args.iter().enumerate() {
+                if id != 0 { write!(f, " ")?; }
+                write!(f, "{}", quote(arg))?;
+            }
+
+            match job.kind {
+                JobKind::And                        => write!(f, " && ")?,
+                JobKind::Or                         => write!(f, " || ")?,
+                JobKind::Background                 => write!(f, " &")?,
+                JobKind::Pipe(RedirectFrom::Stdout) => write!(f, " | ")?,
+                JobKind::Pipe(RedirectFrom::Stderr) => write!(f, " ^| ")?,
+                JobKind::Pipe(RedirectFrom::Both)   => write!(f, " &| ")?,
+                JobKind::Last                       => ()
+            }
+        }
+
+        if let Some(ref stdin) = self.stdin {
+            write!(f, " < {}", quote(&stdin.file))?;
+        }
+
+        if let Some(ref stdout) = self.stdout {
+            let operator = match stdout.from {
+                RedirectFrom::Stdout => ">",
+                RedirectFrom::Stderr => "^>",
+                RedirectFrom::Both   => "&>",
+            };
+            write!(f, " {}{} {}", operator, if stdout.append { ">" } else { "" }, quote(&stdout.file))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a single statement, returning the grammar's error if the statement is invalid. An error
+/// within a condition or a pipeline is given the offset of the token which caused it.
+pub fn parse_statement(code: &str) -> Result<Statement, ParseError> {
     match parse_(code) {
+        Ok(Ok(statement)) => Ok(statement),
+        Ok(Err((offset, why))) => {
+            let before = &code[..offset];
+            Err(ParseError {
+                line:     before.matches('\n').count() + 1,
+                column:   before.rsplit('\n').next().map_or(0, |line| line.chars().count()) + 1,
+                offset:   offset,
+                expected: Some(why).into_iter().collect(),
+            })
+        },
+        Err(why) => Err(why)
+    }
+}
+
+pub fn parse(code: &str) -> Statement {
+    match parse_statement(code) {
 		Ok(code_ok) => code_ok,
 		Err(err) => {
             let stderr = stderr();
@@ -57,9 +130,19 @@ peg_file! grammar("grammar.rustpeg");
 mod tests {
     use super::grammar::*;
     use super::*;
-    use flow_control::Statement;
+    use flow_control::{Case, Statement};
+    use parser::conditionals::{Comparator, Conditional};
     use shell::JobKind;
 
+    #[test]
+    fn quoting_arguments() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("two words"), "'two words'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+        assert_eq!(quote("it's"), "'it'\"'\"'s'");
+    }
+
     #[test]
     fn full_script() {
         pipelines(r#"if a == a
@@ -80,7 +163,7 @@ else
   echo false a == a
 fi
 "#)
-            .unwrap();  // Make sure it parses
+            .unwrap().unwrap();  // Make sure it parses
     }
 
     #[test]
@@ -111,17 +194,17 @@ else
 
 # comment
 
-"#).unwrap();  // Make sure it parses
+"#).unwrap().unwrap();  // Make sure it parses
     }
     #[test]
     fn parsing_ifs() {
         // Default case where spaced normally
-        let parsed_if = if_("if test 1 -eq 2").unwrap();
+        let parsed_if = if_("if test 1 -eq 2").unwrap().unwrap();
         let correct_parse = Statement::If {
-            expression: Pipeline::new(
+            expression: Conditional::Pipeline(Pipeline::new(
                 vec!(Job::new(
                     vec!("test".to_owned(), "1".to_owned(), "-eq".to_owned(), "2".to_owned()), JobKind::Last)
-                ), None, None),
+                ), None, None)),
             success: vec!(),
             else_if: vec!(),
             failure: vec!()
@@ -129,8 +212,22 @@ else
         assert_eq!(correct_parse, parsed_if);
 
         // Trailing spaces after final value
-        let parsed_if = if_("if test 1 -eq 2         ").unwrap();
+        let parsed_if = if_("if test 1 -eq 2         ").unwrap().unwrap();
+        assert_eq!(correct_parse, parsed_if);
+
+        // Native comparisons do not require the test command
+        let parsed_if = if_("if $a == 5").unwrap().unwrap();
+        let correct_parse = Statement::If {
+            expression: Conditional::Comparison("$a".to_owned(), Comparator::Equal, "5".to_owned()),
+            success: vec!(),
+            else_if: vec!(),
+            failure: vec!()
+        };
         assert_eq!(correct_parse, parsed_if);
+
+        // A condition must be supplied
+        assert_eq!(if_("if").unwrap(), Err((2, "expected a condition")));
+        assert_eq!(parse_statement("if true && ").unwrap_err().offset, 11);
     }
 
     #[test]
@@ -169,14 +266,71 @@ else
         assert_eq!(correct_parse, parsed_if);
     }
 
+    #[test]
+    fn parsing_lets() {
+        let parsed_let = let_("let a = 1").unwrap();
+        let correct_parse = Statement::Let { expression: "a = 1".to_owned(), global: false };
+        assert_eq!(correct_parse, parsed_let);
+
+        // The global keyword assigns to the global scope
+        let parsed_let = let_("let global a = 1").unwrap();
+        let correct_parse = Statement::Let { expression: "a = 1".to_owned(), global: true };
+        assert_eq!(correct_parse, parsed_let);
+
+        // A variable may still be named global
+        let parsed_let = let_("let global = 1").unwrap();
+        let correct_parse = Statement::Let { expression: "global = 1".to_owned(), global: false };
+        assert_eq!(correct_parse, parsed_let);
+    }
+
+    #[test]
+    fn parsing_breaks_and_continues() {
+        assert_eq!(Statement::Break(1), break_("break").unwrap());
+        assert_eq!(Statement::Break(1), break_("   break   ").unwrap());
+        assert_eq!(Statement::Break(2), break_("break 2").unwrap());
+        assert!(break_("break 0").is_err());
+
+        assert_eq!(Statement::Continue(1), continue_("continue").unwrap());
+        assert_eq!(Statement::Continue(1), continue_("   continue   ").unwrap());
+        assert_eq!(Statement::Continue(3), continue_("continue 3").unwrap());
+        assert!(continue_("continue 0").is_err());
+    }
+
+    #[test]
+    fn parsing_matches() {
+        let parsed_match = match_("match $subcommand").unwrap();
+        let correct_parse = Statement::Match {
+            expression: "$subcommand".to_owned(),
+            cases:      vec!()
+        };
+        assert_eq!(correct_parse, parsed_match);
+
+        let parsed_case = case_("   case start | run   ").unwrap();
+        let correct_parse = Statement::Case(Case {
+            value:      Some("start | run".to_owned()),
+            statements: vec!()
+        });
+        assert_eq!(correct_parse, parsed_case);
+
+        let parsed_case = case_("case _").unwrap();
+        let correct_parse = Statement::Case(Case { value: None, statements: vec!() });
+        assert_eq!(correct_parse, parsed_case);
+
+        // Patterns that merely begin with an underscore are not the default case
+        let parsed_case = case_("case _*").unwrap();
+        let correct_parse = Statement::Case(Case { value: Some("_*".to_owned()), statements: vec!() });
+        assert_eq!(correct_parse, parsed_case);
+    }
+
     #[test]
     fn parsing_functions() {
         // Default case where spaced normally
         let parsed_if = fn_("fn bob").unwrap();
         let correct_parse = Statement::Function{
-            name:       "bob".to_string(),
-            args:       vec!(),
-            statements: vec!()
+            name:        "bob".to_string(),
+            args:        vec!(),
+            description: String::new(),
+            statements:  vec!()
         };
         assert_eq!(correct_parse, parsed_if);
 
@@ -191,9 +345,10 @@ else
         // Default case where spaced normally
         let parsed_if = fn_("fn bob a b").unwrap();
         let correct_parse = Statement::Function{
-            name:       "bob".to_owned(),
-            args:       vec!("a".to_owned(), "b".to_owned()),
-            statements: vec!()
+            name:        "bob".to_owned(),
+            args:        vec!("a".to_owned(), "b".to_owned()),
+            description: String::new(),
+            statements:  vec!()
         };
         assert_eq!(correct_parse, parsed_if);
 
@@ -204,5 +359,25 @@ else
         // Leading spaces after final value
         let parsed_if = fn_("         fn bob a b").unwrap();
         assert_eq!(correct_parse, parsed_if);
+
+        // Description following the arguments
+        let parsed_if = fn_("fn bob a b -- Says hello to bob").unwrap();
+        let correct_parse = Statement::Function{
+            name:        "bob".to_owned(),
+            args:        vec!("a".to_owned(), "b".to_owned()),
+            description: "Says hello to bob".to_owned(),
+            statements:  vec!()
+        };
+        assert_eq!(correct_parse, parsed_if);
+
+        // Description without any arguments
+        let parsed_if = fn_("fn bob --Says hello to bob  ").unwrap();
+        let correct_parse = Statement::Function{
+            name:        "bob".to_owned(),
+            args:        vec!(),
+            description: "Says hello to bob".to_owned(),
+            statements:  vec!()
+        };
+        assert_eq!(correct_parse, parsed_if);
     }
 }
@@ -44,7 +44,8 @@ fn get_job_kind(args: &str, index: usize, pipe_char_was_found: bool) -> (JobKind
 
 #[allow(cyclomatic_complexity)]
 /// Parses each individual pipeline, separating arguments, pipes, background tasks, and redirections.
-pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
+/// An error is given along with the offset within `args` of the redirection which caused it.
+pub fn collect(possible_error: &mut Option<(usize, &'static str)>, args: &str) -> Pipeline {
     let mut jobs: Vec<Job> = Vec::new();
     let mut args_iter = args.bytes().peekable();
     let (mut index, mut arg_start) = (0, 0);
@@ -54,13 +55,14 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
 
     let (mut in_file, mut out_file) = (None, None);
     let mut mode = RedirMode::False;
+    let mut redirection = 0;
     let mut levels = 0;
 
     macro_rules! redir_check {
         ($from:expr, $file:ident, $name:ident, $is_append:expr) => {{
             if $file.is_none() {
                 if $name.is_empty() {
-                    *possible_error = Some("missing standard output file argument after '>'");
+                    *possible_error = Some((redirection, "missing standard output file argument after '>'"));
                 } else {
                     $file = Some(Redirection {
                         from:   $from,
@@ -75,7 +77,13 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
     'outer: loop {
 
         macro_rules! redir_found {
-            ($kind:expr) => {{ mode = $kind; index += 1; arg_start = index; continue 'outer }}
+            ($kind:expr) => {{
+                redirection = args.len() - args_iter.len() - 1;
+                mode = $kind;
+                index += 1;
+                arg_start = index;
+                continue 'outer
+            }}
         }
 
         macro_rules! job_found {
@@ -167,10 +175,14 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
                 break 'outer
             },
             RedirMode::Stdout(from) | RedirMode::StdoutAppend(from) => {
-                match args_iter.next() {
-                    Some(character) => if character == b'>' { mode = RedirMode::StdoutAppend(from); },
+                match args_iter.peek() {
+                    Some(&b'>') => {
+                        let _ = args_iter.next();
+                        mode = RedirMode::StdoutAppend(from);
+                    },
+                    Some(_) => (),
                     None => {
-                        *possible_error = Some("missing standard output file argument after '>'");
+                        *possible_error = Some((redirection, "missing standard output file argument after '>'"));
                         break 'outer
                     }
                 }
@@ -183,6 +195,7 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
                             if in_file.is_some() {
                                 break 'outer
                             } else {
+                                redirection = args.len() - args_iter.len() - 1;
                                 mode = RedirMode::Stdin;
                                 continue 'outer
                             }
@@ -204,7 +217,7 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
                                 });
                             },
                             b'<' if stdout_file.is_empty() => {
-                                *possible_error = Some("missing standard output file argument after '>'");
+                                *possible_error = Some((redirection, "missing standard output file argument after '>'"));
                                 break 'outer
                             }
                             b'<' => {
@@ -217,6 +230,7 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
                                 if in_file.is_some() {
                                     break 'outer
                                 } else {
+                                    redirection = args.len() - args_iter.len() - 1;
                                     mode = RedirMode::Stdin;
                                     continue 'outer
                                 }
@@ -245,6 +259,7 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
                             if out_file.is_some() {
                                 break 'outer
                             } else {
+                                redirection = args.len() - args_iter.len() - 1;
                                 mode = RedirMode::Stdout(RedirectFrom::Stdout);
                                 continue 'outer
                             }
@@ -266,7 +281,7 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
                                 });
                             },
                             b'>' if stdin_file.is_empty() => {
-                                *possible_error = Some("missing standard input file argument after '<'");
+                                *possible_error = Some((redirection, "missing standard input file argument after '<'"));
                                 break 'outer
                             }
                             b'>' => {
@@ -279,6 +294,7 @@ pub fn collect(possible_error: &mut Option<&str>, args: &str) -> Pipeline {
                                 if out_file.is_some() {
                                     break 'outer
                                 } else {
+                                    redirection = args.len() - args_iter.len() - 1;
                                     mode = RedirMode::Stdin;
                                     continue 'outer
                                 }
@@ -624,4 +640,14 @@ mod tests {
             assert!(false);
         }
     }
+
+    #[test]
+    fn redirection_without_whitespace() {
+        if let Statement::Pipeline(pipeline) = parse("cat <stuff >other") {
+            assert_eq!("stuff", &pipeline.clone().stdin.unwrap().file);
+            assert_eq!("other", &pipeline.clone().stdout.unwrap().file);
+        } else {
+            assert!(false);
+        }
+    }
 }
@@ -2,6 +2,7 @@ const BACKSL: u8 = 1;
 const SQUOTE: u8 = 2;
 const DQUOTE: u8 = 4;
 const TRIM:   u8 = 8;
+const COMM:   u8 = 16;
 
 pub struct QuoteTerminator {
     buffer: String,
@@ -22,15 +23,23 @@ impl QuoteTerminator {
         for character in self.buffer.bytes().skip(self.read) {
             self.read += 1;
             match character {
+                // Quotes within comments, such as an apostrophe, must not begin a quoted string.
+                b'\n' if self.flags & COMM != 0  => self.flags ^= COMM,
+                _ if self.flags & COMM != 0       => (),
                 _ if self.flags & BACKSL != 0     => self.flags ^= BACKSL,
                 b'\\'                             => self.flags ^= BACKSL,
                 b'\'' if self.flags & DQUOTE == 0 => self.flags ^= SQUOTE,
                 b'"'  if self.flags & SQUOTE == 0 => self.flags ^= DQUOTE,
+                b'#'  if self.flags & (SQUOTE + DQUOTE) == 0 => self.flags |= COMM,
                 _ => (),
             }
         }
 
-        if self.flags & (SQUOTE + DQUOTE) != 0 {
+        if self.flags & COMM != 0 {
+            // A comment always ends with its line.
+            self.flags ^= COMM;
+            true
+        } else if self.flags & (SQUOTE + DQUOTE) != 0 {
             self.read += 1;
             self.buffer.push('\n');
             false
@@ -44,5 +53,41 @@ impl QuoteTerminator {
         }
     }
 
+    /// The number of bytes that have been buffered so far.
+    pub fn buffered(&self) -> usize { self.buffer.len() }
+
     pub fn consume(self) -> String { self.buffer }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminate(lines: &[&str]) -> Option<String> {
+        let mut lines = lines.iter();
+        let mut buffer = QuoteTerminator::new(lines.next().unwrap().to_string());
+        while !buffer.is_terminated() {
+            match lines.next() {
+                Some(line) => buffer.append(line.to_string()),
+                None       => return None
+            }
+        }
+        Some(buffer.consume())
+    }
+
+    #[test]
+    fn terminating_quotes() {
+        assert_eq!(terminate(&["echo 'one", "two'"]), Some("echo 'one\ntwo'".to_owned()));
+        assert_eq!(terminate(&["echo \"one'", "two\""]), Some("echo \"one'\ntwo\"".to_owned()));
+        assert_eq!(terminate(&["echo one \\", "    two"]), Some("echo one two".to_owned()));
+        assert_eq!(terminate(&["echo 'one"]), None);
+    }
+
+    #[test]
+    fn quotes_within_comments() {
+        assert_eq!(terminate(&["# don't"]), Some("# don't".to_owned()));
+        assert_eq!(terminate(&["echo one # it's \\"]), Some("echo one # it's \\".to_owned()));
+        assert_eq!(terminate(&["echo '#", "one'"]), Some("echo '#\none'".to_owned()));
+        assert_eq!(terminate(&["echo \\#'", "one'"]), Some("echo \\#'\none'".to_owned()));
+    }
+}
@@ -0,0 +1,600 @@
+//! Parses entire scripts, or chunks of interactive input, into a tree of statements. Each block
+//! statement, such as an `if` or a `for`, contains the statements within its body, so the tree
+//! may be handed to the shell for execution only once the entire input is known to be valid.
+
+use std::cmp;
+use std::fmt;
+use flow_control::{Case, ElseIf, Statement};
+use super::conditionals::Conditional;
+use super::peg::{parse_statement, ParseError};
+use super::statements::{StatementError, StatementSplitter};
+use super::QuoteTerminator;
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    /// A statement could not be split from the rest of its line.
+    Statement(StatementError),
+    /// A statement was split, but could not be parsed by the grammar.
+    Grammar(ParseError),
+    /// The input ended within a quoted string, or after a line ending with a backslash.
+    UnterminatedLine,
+    /// The input ended before every block was closed with an `end`. The `depth` is the number
+    /// of blocks that were left open, and `block` is the keyword of the innermost block.
+    UnterminatedBlock { block: &'static str, depth: usize },
+    /// A keyword was given where it does not belong, such as an `else` outside of an `if`.
+    Unexpected(&'static str),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            // The position is omitted, as it is already given by the `SyntaxError`.
+            ErrorKind::Statement(StatementError::InvalidCharacter(character, _)) => {
+                write!(f, "'{}' is out of place", character)
+            },
+            ErrorKind::Statement(ref error) => write!(f, "{}", error),
+            ErrorKind::Grammar(ref error) => {
+                // Failures that are reported by the actions of the grammar are complete
+                // messages, whereas the remaining failures are the tokens that were expected.
+                let mut expected = error.expected.iter().cloned().collect::<Vec<&str>>();
+                expected.sort();
+                let message = expected.iter()
+                    .find(|token| token.contains(' ') && !token.starts_with('"') && !token.starts_with('['));
+                match message {
+                    Some(message) => write!(f, "{}", message),
+                    None if expected.is_empty() => write!(f, "unexpected input"),
+                    None => write!(f, "expected one of {}", expected.join(", ")),
+                }
+            },
+            ErrorKind::UnterminatedLine => {
+                write!(f, "unexpected end of input within a quoted string or continued line")
+            },
+            ErrorKind::UnterminatedBlock { block, .. } => write!(f, "`{}` block is missing an `end`", block),
+            ErrorKind::Unexpected(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// A syntax error, along with the location in the source at which it was found.
+#[derive(Debug, PartialEq)]
+pub struct SyntaxError {
+    pub kind:   ErrorKind,
+    /// The name of the file that the source was read from.
+    pub file:   String,
+    /// The line of the error, starting from 1.
+    pub line:   usize,
+    /// The column of the error, starting from 1, and counted in characters.
+    pub column: usize,
+    /// The line of source code that contains the error.
+    pub source: String,
+}
+
+impl fmt::Display for SyntaxError {
+    /// Renders the error with the offending line of source code, and a caret beneath the column.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Tabs are retained so that the caret is aligned regardless of the width of a tab.
+        let padding = self.source.chars().take(self.column - 1)
+            .map(|character| if character == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        write!(f, "{}:{}:{}: syntax error: {}\n{}\n{}^", self.file, self.line, self.column, self.kind,
+            self.source, padding)
+    }
+}
+
+impl SyntaxError {
+    /// Renders the error as a warning, for an error which does not prevent the script from being executed.
+    pub fn warning(&self) -> String {
+        format!("{}:{}:{}: warning: {}", self.file, self.line, self.column, self.kind)
+    }
+}
+
+/// The location of a statement, as the index of its line and the byte offset within that line.
+#[derive(Debug, Default, Clone, Copy)]
+struct Span {
+    line:   usize,
+    column: usize,
+}
+
+/// The source of a script, which is used to locate errors.
+struct Source<'a> {
+    file:  &'a str,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Source<'a> {
+    fn error(&self, kind: ErrorKind, span: Span) -> SyntaxError {
+        let source = self.lines.get(span.line).cloned().unwrap_or("");
+        let mut column = span.column.min(source.len());
+        while !source.is_char_boundary(column) { column -= 1; }
+        SyntaxError {
+            kind:   kind,
+            file:   self.file.to_owned(),
+            line:   span.line + 1,
+            column: source[..column].chars().count() + 1,
+            source: source.to_owned(),
+        }
+    }
+}
+
+/// The statement that caused a block to stop being collected.
+enum Terminator {
+    End,
+    Else,
+    ElseIf(ElseIf),
+    Case(Case),
+    Eof,
+}
+
+/// An error found while building the tree, and the span of the statement that caused it.
+type TreeError = (ErrorKind, Span);
+
+/// Parses the given input into a tree of statements. Lines that end within quotes, or that end
+/// with a backslash, are joined with the lines that follow them before being parsed. The `file`
+/// is the name that will be given to the source within errors. An interpreter directive given
+/// by the first line, such as `#!/bin/ion`, is skipped.
+///
+/// Parsing continues after an error is found, so that every error within the input may be
+/// reported at once. If any errors are found, they are returned in the order of their location.
+pub fn parse_script(script: &str, file: &str) -> Result<Vec<Statement>, Vec<SyntaxError>> {
+    let (tree, errors) = parse_tree(script, file);
+    if errors.is_empty() { Ok(tree) } else { Err(errors) }
+}
+
+/// Parses an entire script, as `parse_script` does, except that any blocks which are still open
+/// at the end of the input are closed as if an `end` had been given. Each block that was closed
+/// in this way is returned alongside the tree, so that it may be reported as a warning. Input
+/// which ends within quotes, or after a continued line, is still an error.
+pub fn parse_complete_script(script: &str, file: &str) -> Result<(Vec<Statement>, Vec<SyntaxError>), Vec<SyntaxError>> {
+    let (tree, errors) = parse_tree(script, file);
+    let (warnings, errors): (Vec<SyntaxError>, Vec<SyntaxError>) = errors.into_iter().partition(|error| {
+        match error.kind { ErrorKind::UnterminatedBlock { .. } => true, _ => false }
+    });
+    if errors.is_empty() { Ok((tree, warnings)) } else { Err(errors) }
+}
+
+/// Parses the input into a tree of statements, along with every error that was found, in the
+/// order of their location. Blocks that are never ended contain every statement that follows them.
+fn parse_tree(script: &str, file: &str) -> (Vec<Statement>, Vec<SyntaxError>) {
+    let source = Source { file: file, lines: script.lines().collect() };
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+    let mut id = if script.starts_with("#!") { 1 } else { 0 };
+    'lines: while id < source.lines.len() {
+        let first = id;
+        let mut buffer = QuoteTerminator::new(source.lines[id].to_owned());
+
+        // Records the offset within the joined line at which each physical line begins, along
+        // with how much leading whitespace was trimmed from it, so that offsets within the
+        // joined line may be mapped back onto the physical lines.
+        let mut segments = vec![(0, first, 0)];
+        while !buffer.is_terminated() {
+            id += 1;
+            let line = match source.lines.get(id) {
+                Some(&line) => line,
+                None        => {
+                    errors.push(source.error(ErrorKind::UnterminatedLine, Span { line: first, column: 0 }));
+                    break 'lines;
+                }
+            };
+            let start = buffer.buffered();
+            buffer.append(line.to_owned());
+            let trimmed = if buffer.buffered() - start == line.len() { 0 } else { line.len() - line.trim_left().len() };
+            segments.push((start, id, trimmed));
+        }
+        id += 1;
+
+        let line = buffer.consume();
+        let span_of = |offset: usize| {
+            let &(start, line_id, trimmed) = segments.iter().rev().find(|&&(start, _, _)| start <= offset).unwrap();
+            Span { line: line_id, column: offset - start + trimmed }
+        };
+
+        let mut splitter = StatementSplitter::new(&line);
+        loop {
+            let start = splitter.offset();
+            let statement = match splitter.next() {
+                Some(statement) => statement,
+                None            => break
+            };
+            let remainder = &line[start..];
+            let start = start + remainder.len() - remainder.trim_left().len();
+
+            match statement {
+                Ok(statement) => match parse_statement(statement) {
+                    Ok(Statement::Default) => (),
+                    Ok(statement)          => statements.push((statement, span_of(start))),
+                    Err(why) => {
+                        let offset = start + why.offset;
+                        errors.push(source.error(ErrorKind::Grammar(why), span_of(offset)));
+                    }
+                },
+                Err(why) => {
+                    let offset = match why {
+                        StatementError::InvalidCharacter(_, position) => position - 1,
+                        _ => start
+                    };
+                    errors.push(source.error(ErrorKind::Statement(why), span_of(offset)));
+                }
+            }
+        }
+    }
+
+    let mut tree_errors = Vec::new();
+    let mut statements = statements.into_iter();
+    let mut tree = Vec::new();
+    loop {
+        let (mut block, terminator, span) = parse_block(&mut statements, 0, 0, &mut tree_errors);
+        tree.append(&mut block);
+        match terminator {
+            Terminator::Eof => break,
+            _ => tree_errors.push(unexpected(&terminator, span))
+        }
+    }
+
+    errors.extend(tree_errors.into_iter().map(|(kind, span)| source.error(kind, span)));
+    errors.sort_by_key(|error| (error.line, error.column));
+    (tree, errors)
+}
+
+/// Determines whether the input is valid so far, but must be followed by more input because it
+/// ends within a block, a quoted string, or a continued line. If so, the number of blocks that
+/// are open at the end of the input is returned.
+pub fn incomplete_depth(script: &str) -> Option<usize> {
+    let errors = match parse_script(script, "") {
+        Ok(_)       => return None,
+        Err(errors) => errors
+    };
+
+    let mut depth = 0;
+    for error in errors {
+        match error.kind {
+            ErrorKind::UnterminatedBlock { depth: block_depth, .. } => depth = cmp::max(depth, block_depth),
+            ErrorKind::UnterminatedLine => (),
+            _ => return None
+        }
+    }
+    Some(depth)
+}
+
+/// The error for a terminator that was found where it may not be used.
+fn unexpected(terminator: &Terminator, span: Span) -> TreeError {
+    let message = match *terminator {
+        Terminator::End                          => "`end` given without a block to end",
+        Terminator::Else | Terminator::ElseIf(_) => "`else` given outside of an `if` block",
+        Terminator::Case(_)                      => "`case` given outside of a `match` block",
+        Terminator::Eof                          => unreachable!(),
+    };
+    (ErrorKind::Unexpected(message), span)
+}
+
+/// The error for a block, opened by the statement at the given span, which was never ended.
+fn unterminated(block: &'static str, span: Span, depth: usize) -> TreeError {
+    (ErrorKind::UnterminatedBlock { block: block, depth: depth }, span)
+}
+
+/// The error for a `break` or `continue` which applies to more loops than enclose it, where the
+/// loops of a function body do not include those around the function.
+fn outside_loop(statement: &Statement, loops: usize, span: Span) -> Option<TreeError> {
+    let (levels, message) = match *statement {
+        Statement::Break(levels)    => (levels, "`break` given outside of a loop"),
+        Statement::Continue(levels) => (levels, "`continue` given outside of a loop"),
+        _ => return None
+    };
+    if levels > loops { Some((ErrorKind::Unexpected(message), span)) } else { None }
+}
+
+/// Collects statements into a block until a terminator is found, recursively parsing the
+/// bodies of any blocks that are opened along the way. The span of the terminator is returned
+/// alongside it. The number of loops which enclose the block is given by `loops`.
+fn parse_block<I>(statements: &mut I, depth: usize, loops: usize, errors: &mut Vec<TreeError>)
+    -> (Vec<Statement>, Terminator, Span)
+    where I: Iterator<Item = (Statement, Span)>
+{
+    let mut block = Vec::new();
+    while let Some((statement, span)) = statements.next() {
+        let statement = match statement {
+            Statement::End            => return (block, Terminator::End, span),
+            Statement::Else           => return (block, Terminator::Else, span),
+            Statement::ElseIf(elseif) => return (block, Terminator::ElseIf(elseif), span),
+            Statement::Case(case)     => return (block, Terminator::Case(case), span),
+            Statement::If { expression, .. } => parse_if(statements, expression, span, depth + 1, loops, errors),
+            Statement::Match { expression, .. } => parse_match(statements, expression, span, depth + 1, loops, errors),
+            Statement::While { expression, .. } => Statement::While {
+                expression: expression,
+                statements: parse_body(statements, "while", span, depth + 1, loops + 1, errors)
+            },
+            Statement::For { variable, values, .. } => Statement::For {
+                variable:   variable,
+                values:     values,
+                statements: parse_body(statements, "for", span, depth + 1, loops + 1, errors)
+            },
+            Statement::Function { name, args, description, .. } => Statement::Function {
+                name:        name,
+                args:        args,
+                description: description,
+                statements:  parse_body(statements, "fn", span, depth + 1, 0, errors)
+            },
+            statement => {
+                if let Some(error) = outside_loop(&statement, loops, span) { errors.push(error); }
+                statement
+            }
+        };
+        block.push(statement);
+    }
+    (block, Terminator::Eof, Span::default())
+}
+
+/// Parses the body of a block that may only be terminated by an `end`.
+fn parse_body<I>(statements: &mut I, block: &'static str, span: Span, depth: usize, loops: usize,
+    errors: &mut Vec<TreeError>) -> Vec<Statement>
+    where I: Iterator<Item = (Statement, Span)>
+{
+    let mut body = Vec::new();
+    loop {
+        let (mut contents, terminator, terminator_span) = parse_block(statements, depth, loops, errors);
+        body.append(&mut contents);
+        match terminator {
+            Terminator::End => return body,
+            Terminator::Eof => {
+                errors.push(unterminated(block, span, depth));
+                return body;
+            },
+            _ => errors.push(unexpected(&terminator, terminator_span))
+        }
+    }
+}
+
+fn parse_if<I>(statements: &mut I, expression: Conditional, span: Span, depth: usize, loops: usize,
+    errors: &mut Vec<TreeError>) -> Statement
+    where I: Iterator<Item = (Statement, Span)>
+{
+    let (mut success, mut terminator, mut terminator_span) = parse_block(statements, depth, loops, errors);
+    let mut else_if: Vec<ElseIf> = Vec::new();
+    let mut failure = Vec::new();
+    let mut in_else = false;
+    loop {
+        match terminator {
+            Terminator::End => break,
+            Terminator::Eof => {
+                errors.push(unterminated("if", span, depth));
+                break
+            },
+            Terminator::ElseIf(elseif) => if in_else {
+                errors.push((ErrorKind::Unexpected("else block already given"), terminator_span));
+            } else {
+                else_if.push(elseif);
+            },
+            Terminator::Else => if in_else {
+                errors.push((ErrorKind::Unexpected("else block already given"), terminator_span));
+            } else {
+                in_else = true;
+            },
+            Terminator::Case(_) => errors.push(unexpected(&terminator, terminator_span)),
+        }
+
+        let (mut block, next, next_span) = parse_block(statements, depth, loops, errors);
+        if in_else {
+            failure.append(&mut block);
+        } else if let Some(elseif) = else_if.last_mut() {
+            elseif.success.append(&mut block);
+        } else {
+            success.append(&mut block);
+        }
+        terminator = next;
+        terminator_span = next_span;
+    }
+
+    Statement::If { expression: expression, success: success, else_if: else_if, failure: failure }
+}
+
+fn parse_match<I>(statements: &mut I, expression: String, span: Span, depth: usize, loops: usize,
+    errors: &mut Vec<TreeError>) -> Statement
+    where I: Iterator<Item = (Statement, Span)>
+{
+    let (block, mut terminator, mut terminator_span) = parse_block(statements, depth, loops, errors);
+    if !block.is_empty() {
+        errors.push((ErrorKind::Unexpected("statements within a match must belong to a case"), span));
+    }
+
+    let mut cases: Vec<Case> = Vec::new();
+    loop {
+        match terminator {
+            Terminator::End => break,
+            Terminator::Eof => {
+                errors.push(unterminated("match", span, depth));
+                break
+            },
+            Terminator::Case(case) => cases.push(case),
+            _ => errors.push(unexpected(&terminator, terminator_span))
+        }
+
+        let (mut block, next, next_span) = parse_block(statements, depth, loops, errors);
+        if let Some(case) = cases.last_mut() {
+            case.statements.append(&mut block);
+        }
+        terminator = next;
+        terminator_span = next_span;
+    }
+
+    Statement::Match { expression: expression, cases: cases }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_helpers::pipeline;
+
+    fn echo(value: &str) -> Statement {
+        Statement::Pipeline(pipeline(&["echo", value]))
+    }
+
+    fn parse(script: &str) -> Result<Vec<Statement>, Vec<ErrorKind>> {
+        parse_script(script, "test.ion").map_err(|errors| errors.into_iter().map(|error| error.kind).collect())
+    }
+
+    fn locate(script: &str) -> Vec<(usize, usize)> {
+        match parse_script(script, "test.ion") {
+            Ok(_)      => panic!("{:?} was parsed without an error", script),
+            Err(errors) => errors.into_iter().map(|error| (error.line, error.column)).collect()
+        }
+    }
+
+    #[test]
+    fn parsing_nested_blocks() {
+        let script = "for i in 1..3\n    if test $i -eq 1\n        fn one\n            echo 1\n        end\n    \
+            else\n        echo 2\n    end\nend\necho done";
+        let expected = vec![
+            Statement::For {
+                variable:   "i".to_owned(),
+                values:     vec!["1..3".to_owned()],
+                statements: vec![Statement::If {
+                    expression: Conditional::Pipeline(pipeline(&["test", "$i", "-eq", "1"])),
+                    success:    vec![Statement::Function {
+                        name:        "one".to_owned(),
+                        args:        vec![],
+                        description: String::new(),
+                        statements:  vec![echo("1")]
+                    }],
+                    else_if:    vec![],
+                    failure:    vec![echo("2")]
+                }]
+            },
+            echo("done")
+        ];
+        assert_eq!(parse(script), Ok(expected));
+    }
+
+    #[test]
+    fn parsing_if_branches() {
+        let script = "if test 1; echo 1; else if test 2; echo 2; else; echo 3; end";
+        match parse(script) {
+            Ok(ref statements) if statements.len() == 1 => match statements[0] {
+                Statement::If { ref success, ref else_if, ref failure, .. } => {
+                    assert_eq!(success, &vec![echo("1")]);
+                    assert_eq!(else_if.len(), 1);
+                    assert_eq!(else_if[0].success, vec![echo("2")]);
+                    assert_eq!(failure, &vec![echo("3")]);
+                },
+                ref statement => panic!("expected an if statement, found {:?}", statement)
+            },
+            result => panic!("expected a single statement, found {:?}", result)
+        }
+    }
+
+    #[test]
+    fn parsing_match_cases() {
+        let script = "match $a\n    # Comments may precede the first case\n    case 1\n        echo 1\n    \
+            case _\n        echo 2\nend";
+        let expected = vec![Statement::Match {
+            expression: "$a".to_owned(),
+            cases: vec![
+                Case { value: Some("1".to_owned()), statements: vec![echo("1")] },
+                Case { value: None, statements: vec![echo("2")] },
+            ]
+        }];
+        assert_eq!(parse(script), Ok(expected));
+    }
+
+    #[test]
+    fn skipping_directives_and_comments() {
+        assert_eq!(parse("#!/usr/bin/env ion\necho 1 # don't\n# it's 'two'\necho 3"),
+            Ok(vec![echo("1"), echo("3")]));
+        assert_eq!(locate("#!/bin/ion\necho (one)"), vec![(2, 6)]);
+    }
+
+    #[test]
+    fn unterminated_input() {
+        assert_eq!(parse("while test 1\n    for i in 1..3"), Err(vec![
+            ErrorKind::UnterminatedBlock { block: "while", depth: 1 },
+            ErrorKind::UnterminatedBlock { block: "for", depth: 2 },
+        ]));
+        assert_eq!(parse("if test 1\n    echo 1\nelse"),
+            Err(vec![ErrorKind::UnterminatedBlock { block: "if", depth: 1 }]));
+        assert_eq!(parse("match $a"), Err(vec![ErrorKind::UnterminatedBlock { block: "match", depth: 1 }]));
+        assert_eq!(parse("echo 'one\ntwo"), Err(vec![ErrorKind::UnterminatedLine]));
+    }
+
+    #[test]
+    fn closing_blocks_at_the_end_of_input() {
+        let script = "echo 1\nfor i in 1..3\n    if test $i -eq 1\n        echo 2";
+        let (statements, warnings) = parse_complete_script(script, "test.ion").unwrap();
+        assert_eq!(statements, vec![echo("1"), Statement::For {
+            variable:   "i".to_owned(),
+            values:     vec!["1..3".to_owned()],
+            statements: vec![Statement::If {
+                expression: Conditional::Pipeline(pipeline(&["test", "$i", "-eq", "1"])),
+                success:    vec![echo("2")],
+                else_if:    vec![],
+                failure:    vec![]
+            }]
+        }]);
+        assert_eq!(warnings.iter().map(|warning| (warning.line, warning.column)).collect::<Vec<_>>(), vec![(2, 1), (3, 5)]);
+        assert_eq!(warnings[0].warning(), "test.ion:2:1: warning: `for` block is missing an `end`");
+
+        assert_eq!(parse_complete_script("echo 1\nif test 1; echo 'two", "test.ion").unwrap_err()[0].kind,
+            ErrorKind::UnterminatedLine);
+        assert!(parse_complete_script("for i in 1..3\n    echo (one)", "test.ion").is_err());
+    }
+
+    #[test]
+    fn incomplete_input() {
+        assert_eq!(incomplete_depth("while test 1\n    for i in 1..3"), Some(2));
+        assert_eq!(incomplete_depth("if test 1\n    echo 1\nelse"), Some(1));
+        assert_eq!(incomplete_depth("echo 'one\ntwo"), Some(0));
+        assert_eq!(incomplete_depth("if test 1\n    echo 1\nend"), None);
+        assert_eq!(incomplete_depth("if test 1\n    echo (one)"), None);
+    }
+
+    #[test]
+    fn unexpected_keywords() {
+        assert!(parse("echo 1\nend").is_err());
+        assert!(parse("else").is_err());
+        assert!(parse("case 1").is_err());
+        assert!(parse("for i in 1..3; case 1; end").is_err());
+        assert!(parse("match $a; echo 1; case 1; end").is_err());
+        assert_eq!(parse("if test 1; else; echo 1; else; end"),
+            Err(vec![ErrorKind::Unexpected("else block already given")]));
+    }
+
+    #[test]
+    fn nothing_is_returned_on_error() {
+        assert!(parse("echo 1\necho (two)\necho 3").is_err());
+    }
+
+    #[test]
+    fn locating_errors() {
+        assert_eq!(locate("echo 1\n  echo (two)"), vec![(2, 8)]);
+        assert_eq!(locate("echo 1; echo 2; end"), vec![(1, 17)]);
+        assert_eq!(locate("echo 1\nfor i in 1..3\n    echo $i\n"), vec![(2, 1)]);
+        assert_eq!(locate("if test 1\n    echo 1\nelse\n    echo 2\nelse\nend"), vec![(5, 1)]);
+        assert_eq!(locate("echo 'one\ntwo' \\\n    three; end"), vec![(3, 12)]);
+        assert_eq!(locate("echo \"one\n\ttwo\"; echo $(three"), vec![(2, 8)]);
+    }
+
+    #[test]
+    fn locating_breaks_outside_of_loops() {
+        assert_eq!(locate("echo 1\nbreak\necho 2"), vec![(2, 1)]);
+        assert_eq!(locate("while true\n    fn f\n        continue\n    end\nend"), vec![(3, 9)]);
+        assert_eq!(locate("for i in 1..3\n    if test $i -eq 2; break 2; end\nend"), vec![(2, 23)]);
+        assert!(parse("for i in 1..3\n    while true\n        break 2\n    end\nend").is_ok());
+    }
+
+    #[test]
+    fn locating_errors_within_statements() {
+        assert_eq!(locate("echo 1\n  cat file >"), vec![(2, 12)]);
+        assert_eq!(locate("if test 1 && cat >"), vec![(1, 18)]);
+        assert_eq!(locate("echo 1; while true &&"), vec![(1, 22)]);
+    }
+
+    #[test]
+    fn reporting_every_error() {
+        let script = "end\nfor i in 1..3\n    echo (one)\n    case 2\n    echo 3\nend\necho )four\nif test 5";
+        assert_eq!(locate(script), vec![(1, 1), (3, 10), (4, 5), (7, 6), (8, 1)]);
+    }
+
+    #[test]
+    fn rendering_errors() {
+        let errors = parse_script("echo 1\n\techo (two)", "test.ion").unwrap_err();
+        assert_eq!(errors[0].to_string(), "test.ion:2:7: syntax error: '(' is out of place\n\techo (two)\n\t     ^");
+    }
+}
@@ -2,15 +2,14 @@ extern crate permutate;
 
 mod braces;
 mod process;
-mod ranges;
+pub mod ranges;
 mod words;
 
 use self::braces::BraceToken;
 use self::process::{CommandExpander, CommandToken};
 use self::ranges::parse_range;
-use self::words::{WordIterator, WordToken};
 
-pub use self::words::Index;
+pub use self::words::{Index, WordIterator, WordToken};
 
 pub struct ExpanderFunctions<'f> {
     pub tilde:    &'f Fn(&str) -> Option<String>,
@@ -19,9 +18,9 @@ pub struct ExpanderFunctions<'f> {
     pub command:  &'f Fn(&str, bool) -> Option<String>
 }
 
-fn expand_process(current: &mut String, command: &str, quoted: bool,
-    expand_func: &ExpanderFunctions)
-{
+/// Expands the variables within a command that is to be executed by a subshell, as the
+/// subshell will not have access to the local variables of this shell.
+pub fn expand_command(command: &str, quoted: bool, expand_func: &ExpanderFunctions) -> String {
     let mut expanded = String::with_capacity(command.len());
     for token in CommandExpander::new(command) {
         match token {
@@ -33,7 +32,13 @@ fn expand_process(current: &mut String, command: &str, quoted: bool,
             }
         }
     }
+    expanded
+}
 
+fn expand_process(current: &mut String, command: &str, quoted: bool,
+    expand_func: &ExpanderFunctions)
+{
+    let expanded = expand_command(command, quoted, expand_func);
     if let Some(result) = (expand_func.command)(&expanded, quoted) {
         current.push_str(&result);
     }
@@ -47,7 +52,7 @@ fn expand_brace(current: &mut String, expanders: &mut Vec<Vec<String>>,
     for word in nodes.into_iter()
         .flat_map(|node| expand_string(node, expand_func, reverse_quoting))
     {
-        match parse_range(&word) {
+        match parse_range(&word, true) {
             Some(elements) => for word in elements { temp.push(word) },
             None           => temp.push(word),
         }
@@ -1,60 +1,234 @@
-pub fn parse_range(input: &str) -> Option<Vec<String>> {
-    let mut bytes_iterator = input.bytes().enumerate();
-    while let Some((id, byte)) = bytes_iterator.next() {
-        match byte {
-            b'0'...b'9' | b'-' | b'a'...b'z' | b'A'...b'Z' => continue,
-            b'.' => {
-                let first = &input[..id];
-
-                let mut dots = 1;
-                while let Some((_, byte)) = bytes_iterator.next() {
-                    if byte == b'.' { dots += 1 } else { break }
-                }
-
-                if dots != 2 { break }
-
-                let end = &input[id+dots..];
-
-                if let Ok(start) = first.parse::<isize>() {
-                    if let Ok(end) = end.parse::<isize>() {
-                        return if start < end {
-                            Some((start..end+1).map(|x| x.to_string()).collect())
-                        } else if start > end {
-                            Some((end..start+1).rev().map(|x| x.to_string()).collect())
-                        } else {
-                            Some(vec![first.to_owned()])
-                        }
-                    }
-                } else if first.len() == 1 && end.len() == 1 {
-                    let start = first.bytes().next().unwrap();
-                    let end = end.bytes().next().unwrap();
-
-                    let is_valid = ((start >= b'a' && start <= b'z') && (end >= b'a' && end <= b'z'))
-                     || ((start >= b'A' && start <= b'Z') && (end >= b'A' && end <= b'Z'));
-
-                    if !is_valid { break }
-                    return if start < end {
-                        Some((start..end+1).map(|x| {
-                            let mut output = String::with_capacity(1);
-                            output.push(x as char);
-                            output
-                        }).collect())
-                    } else if start > end {
-                        Some((end..start+1).rev().map(|x| {
-                            let mut output = String::with_capacity(1);
-                            output.push(x as char);
-                            output
-                        }).collect())
-                    } else {
-                        Some(vec![first.to_owned()])
-                    }
-                } else {
-                    break
-                }
-            },
-            _ => break
+//! Range expressions, which are shared by brace expansions, such as `{1..10}`, and by `for` loops,
+//! such as `for i in 10..0..2`. A range has a start and an end bound, which may either both be
+//! integers or both be alphabetic characters of the same case, and an optional step. Ranges
+//! count downwards when the start is greater than the end.
+
+/// The kind of values that a `Range` will produce.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Numeric,
+    Character,
+}
+
+/// A lazily-evaluated sequence of values, which is produced by `parse_range`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    kind:    Kind,
+    /// The next value to be produced, or `None` if the range has been exhausted.
+    current: Option<isize>,
+    /// The inclusive bound at which the range ends.
+    last:    isize,
+    /// The signed distance between each value, which is negative for descending ranges.
+    step:    isize,
+}
+
+impl Range {
+    fn new(kind: Kind, start: isize, end: isize, step: isize, inclusive: bool) -> Range {
+        let step = if start <= end { step } else { -step };
+        let last = if inclusive { Some(end) } else if start < end { Some(end - 1) }
+            else if start > end { Some(end + 1) } else { None };
+
+        match last {
+            Some(last) => Range { kind: kind, current: Some(start), last: last, step: step },
+            None       => Range { kind: kind, current: None, last: start, step: step },
         }
     }
 
-    None
+    fn format(&self, value: isize) -> String {
+        match self.kind {
+            Kind::Numeric   => value.to_string(),
+            Kind::Character => (value as u8 as char).to_string(),
+        }
+    }
+
+    /// Checks if the given value would be produced by this range, without iterating it.
+    pub fn contains(&self, value: &str) -> bool {
+        let start = match self.current {
+            Some(start) => start,
+            None        => return false
+        };
+
+        let value = match self.kind {
+            Kind::Numeric => match value.parse::<isize>() {
+                Ok(value) => value,
+                Err(_)    => return false
+            },
+            Kind::Character if value.len() == 1 => value.as_bytes()[0] as isize,
+            Kind::Character => return false
+        };
+
+        let within = if self.step > 0 {
+            value >= start && value <= self.last
+        } else {
+            value <= start && value >= self.last
+        };
+
+        within && (value - start) % self.step == 0
+    }
+}
+
+impl Iterator for Range {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let current = match self.current {
+            Some(current) => current,
+            None          => return None
+        };
+
+        self.current = current.checked_add(self.step).and_then(|next| {
+            let within = if self.step > 0 { next <= self.last } else { next >= self.last };
+            if within { Some(next) } else { None }
+        });
+
+        Some(self.format(current))
+    }
+}
+
+/// Splits a range expression into its start, the number of dots which follow the start, and
+/// the remainder of the expression.
+fn split_bounds(input: &str) -> Option<(&str, usize, &str)> {
+    // The first byte is skipped so that a negative start is not mistaken for a separator.
+    let id = match input.bytes().skip(1).position(|byte| byte == b'.') {
+        Some(id) => id + 1,
+        None     => return None
+    };
+    let dots = input[id..].bytes().take_while(|&byte| byte == b'.').count();
+    Some((&input[..id], dots, &input[id+dots..]))
+}
+
+fn is_lowercase(byte: u8) -> bool { byte >= b'a' && byte <= b'z' }
+
+fn is_uppercase(byte: u8) -> bool { byte >= b'A' && byte <= b'Z' }
+
+fn parse_character(input: &str) -> Option<u8> {
+    if input.len() != 1 { return None }
+    let byte = input.as_bytes()[0];
+    if is_lowercase(byte) || is_uppercase(byte) { Some(byte) } else { None }
+}
+
+/// Parses a range expression, such as `1..10`, `a...z`, `-5..5`, `10..1`, or `0..100..5`. The
+/// `..` separator will be inclusive of the end if `inclusive` is set, as it is in brace
+/// expansions, and is otherwise exclusive, as it is in `for` loops. The `...` separator is
+/// always inclusive. If a step is given, it must be a non-zero integer, and its sign is ignored
+/// as the direction of the range is determined by its bounds.
+pub fn parse_range(input: &str, inclusive: bool) -> Option<Range> {
+    let (start, dots, remainder) = match split_bounds(input) {
+        Some(bounds) => bounds,
+        None         => return None
+    };
+
+    let inclusive = match dots {
+        2 => inclusive,
+        3 => true,
+        _ => return None
+    };
+
+    let (end, step) = match remainder.find("..") {
+        Some(id) => match remainder[id+2..].parse::<isize>() {
+            Ok(step) if step != 0 => (&remainder[..id], step.abs()),
+            _ => return None
+        },
+        None => (remainder, 1)
+    };
+
+    if let (Ok(start), Ok(end)) = (start.parse::<isize>(), end.parse::<isize>()) {
+        return Some(Range::new(Kind::Numeric, start, end, step, inclusive));
+    }
+
+    match (parse_character(start), parse_character(end)) {
+        (Some(start), Some(end)) if is_lowercase(start) == is_lowercase(end) => {
+            Some(Range::new(Kind::Character, start as isize, end as isize, step, inclusive))
+        },
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(input: &str, inclusive: bool) -> Option<Vec<String>> {
+        parse_range(input, inclusive).map(|range| range.collect())
+    }
+
+    fn values(values: &[&str]) -> Option<Vec<String>> {
+        Some(values.iter().map(|&value| value.to_owned()).collect())
+    }
+
+    #[test]
+    fn ascending_ranges() {
+        assert_eq!(range("1..5", true), values(&["1", "2", "3", "4", "5"]));
+        assert_eq!(range("1..5", false), values(&["1", "2", "3", "4"]));
+        assert_eq!(range("1...5", false), values(&["1", "2", "3", "4", "5"]));
+        assert_eq!(range("3..3", true), values(&["3"]));
+        assert_eq!(range("3..3", false), values(&[]));
+    }
+
+    #[test]
+    fn descending_ranges() {
+        assert_eq!(range("5..1", true), values(&["5", "4", "3", "2", "1"]));
+        assert_eq!(range("5..1", false), values(&["5", "4", "3", "2"]));
+        assert_eq!(range("5...1", false), values(&["5", "4", "3", "2", "1"]));
+    }
+
+    #[test]
+    fn negative_ranges() {
+        assert_eq!(range("-2..2", true), values(&["-2", "-1", "0", "1", "2"]));
+        assert_eq!(range("2..-2", false), values(&["2", "1", "0", "-1"]));
+        assert_eq!(range("-1...-3", false), values(&["-1", "-2", "-3"]));
+    }
+
+    #[test]
+    fn stepped_ranges() {
+        assert_eq!(range("0..20..5", false), values(&["0", "5", "10", "15"]));
+        assert_eq!(range("0...20..5", false), values(&["0", "5", "10", "15", "20"]));
+        assert_eq!(range("0..10..4", true), values(&["0", "4", "8"]));
+        assert_eq!(range("10..0..3", true), values(&["10", "7", "4", "1"]));
+        assert_eq!(range("10..0..-3", true), values(&["10", "7", "4", "1"]));
+        assert_eq!(range("a..g..2", true), values(&["a", "c", "e", "g"]));
+        assert_eq!(range("0..10..0", true), None);
+        assert_eq!(range("0..10..a", true), None);
+    }
+
+    #[test]
+    fn character_ranges() {
+        assert_eq!(range("a..e", true), values(&["a", "b", "c", "d", "e"]));
+        assert_eq!(range("a..e", false), values(&["a", "b", "c", "d"]));
+        assert_eq!(range("E..A", true), values(&["E", "D", "C", "B", "A"]));
+        assert_eq!(range("a..Z", true), None);
+        assert_eq!(range("a..5", true), None);
+        assert_eq!(range("!..~", true), None);
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        assert_eq!(range("1", true), None);
+        assert_eq!(range("..5", true), None);
+        assert_eq!(range("1..", true), None);
+        assert_eq!(range("1.5", true), None);
+        assert_eq!(range("1....5", true), None);
+        assert_eq!(range("ab..cd", true), None);
+        assert_eq!(range("1 2 3", true), None);
+    }
+
+    #[test]
+    fn range_contains() {
+        let range = parse_range("0..10..2", false).unwrap();
+        assert!(range.contains("0"));
+        assert!(range.contains("8"));
+        assert!(!range.contains("10"));
+        assert!(!range.contains("3"));
+        assert!(!range.contains("a"));
+
+        let range = parse_range("5...-5", false).unwrap();
+        assert!(range.contains("5"));
+        assert!(range.contains("-5"));
+        assert!(!range.contains("6"));
+
+        let range = parse_range("a..z", false).unwrap();
+        assert!(range.contains("m"));
+        assert!(!range.contains("z"));
+        assert!(!range.contains("M"));
+    }
 }
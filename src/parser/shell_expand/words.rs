@@ -91,8 +91,9 @@ impl<'a> WordIterator<'a> {
             self.read += 1;
         }
 
-        // The validator at the frontend should catch unterminated braced variables.
-        panic!("ion: fatal error with syntax validation parsing: unterminated braced variable");
+        // The validator at the frontend should catch unterminated braced variables, but the
+        // highlighter reads the command while it is being typed.
+        WordToken::Normal(&self.data[start - 2..])
     }
 
     /// Contains the logic for parsing variable syntax
@@ -154,7 +155,7 @@ impl<'a> WordIterator<'a> {
                 b'\'' if self.flags & DQUOTE == 0 => self.flags ^= SQUOTE,
                 b'"'  if self.flags & SQUOTE == 0 => self.flags ^= DQUOTE,
                 b'$'  if self.flags & SQUOTE == 0 => {
-                    if self.data.as_bytes()[self.read+1] == b'(' {
+                    if self.data.as_bytes().get(self.read+1) == Some(&b'(') {
                         level += 1;
                     }
                 },
@@ -172,8 +173,9 @@ impl<'a> WordIterator<'a> {
             self.read += 1;
         }
 
-        // The validator at the frontend should catch unterminated processes.
-        panic!("ion: fatal error with syntax validation: unterminated process");
+        // The validator at the frontend should catch unterminated processes, but the highlighter
+        // reads the command while it is being typed.
+        WordToken::Normal(&self.data[start - 2..])
     }
 
     /// Contains the logic for parsing array subshell syntax.
@@ -189,7 +191,7 @@ impl<'a> WordIterator<'a> {
                 b'\'' if self.flags & DQUOTE == 0 => self.flags ^= SQUOTE,
                 b'"'  if self.flags & SQUOTE == 0 => self.flags ^= DQUOTE,
                 b'@'  if self.flags & SQUOTE == 0 => {
-                    if self.data.as_bytes()[self.read+1] == b'[' {
+                    if self.data.as_bytes().get(self.read+1) == Some(&b'[') {
                         level += 1;
                     }
                 },
@@ -208,14 +210,16 @@ impl<'a> WordIterator<'a> {
             self.read += 1;
         }
 
-        // The validator at the frontend should catch unterminated processes.
-        panic!("ion: fatal error with syntax validation: unterminated array process");
+        // The validator at the frontend should catch unterminated processes, but the highlighter
+        // reads the command while it is being typed.
+        WordToken::Normal(&self.data[start - 2..])
     }
 
     /// Contains the grammar for parsing brace expansion syntax
     fn braces<I>(&mut self, iterator: &mut I) -> WordToken<'a>
         where I: Iterator<Item = u8>
     {
+        let open = self.read - 1;
         let mut start = self.read;
         let mut level = 0;
         let mut elements = Vec::new();
@@ -245,7 +249,9 @@ impl<'a> WordIterator<'a> {
             self.read += 1;
         }
 
-        panic!("ion: fatal error with syntax validation: unterminated brace")
+        // The validator at the frontend should catch unterminated braces, but the highlighter
+        // reads the command while it is being typed.
+        WordToken::Normal(&self.data[open..])
     }
 }
 
@@ -371,6 +377,19 @@ mod tests {
         assert_eq!(expected.len(), correct);
     }
 
+    #[test]
+    fn words_with_unterminated_expansions() {
+        let input = "echo {a,b $(echo one";
+        let expected = vec![
+            WordToken::Normal("echo"),
+            WordToken::Whitespace(" "),
+            WordToken::Normal("{a,b $(echo one"),
+        ];
+        compare(input, expected);
+        compare("@[echo @", vec![WordToken::Normal("@[echo @")]);
+        compare("a${b", vec![WordToken::Normal("a"), WordToken::Normal("${b")]);
+    }
+
     #[test]
     fn words_process_recursion() {
         let input = "echo $(echo $(echo one)) $(echo one $(echo two) three)";
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::io::{self, Write};
 use flow_control::Statement;
 use super::peg::parse;
@@ -17,27 +18,25 @@ pub enum StatementError {
     UnterminatedBrace,
 }
 
+impl fmt::Display for StatementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StatementError::InvalidCharacter(character, position) => {
+                write!(f, "'{}' at position {} is out of place", character, position)
+            },
+            StatementError::UnterminatedSubshell  => write!(f, "unterminated subshell"),
+            StatementError::UnterminatedBrace     => write!(f, "unterminated brace"),
+            StatementError::UnterminatedBracedVar => write!(f, "unterminated braced var"),
+        }
+    }
+}
+
 pub fn check_statement(statement: Result<&str, StatementError>) -> Option<Statement> {
     match statement {
         Ok(statement) => Some(parse(statement)),
         Err(err) => {
             let stderr = io::stderr();
-            match err {
-                StatementError::InvalidCharacter(character, position) => {
-                    let _ = writeln!(stderr.lock(),
-                        "ion: syntax error: '{}' at position {} is out of place",
-                        character, position);
-                },
-                StatementError::UnterminatedSubshell => {
-                    let _ = writeln!(stderr.lock(), "ion: syntax error: unterminated subshell");
-                },
-                StatementError::UnterminatedBrace => {
-                    let _ = writeln!(stderr.lock(), "ion: syntax error: unterminated brace");
-                },
-                StatementError::UnterminatedBracedVar => {
-                    let _ = writeln!(stderr.lock(), "ion: syntax error: unterminated braced var");
-                }
-            }
+            let _ = writeln!(stderr.lock(), "ion: syntax error: {}", err);
             None
         }
     }
@@ -63,6 +62,9 @@ impl<'a> StatementSplitter<'a> {
             brace_level: 0
         }
     }
+
+    /// The byte offset within the data at which the next statement will begin.
+    pub fn offset(&self) -> usize { self.read }
 }
 
 impl<'a> Iterator for StatementSplitter<'a> {
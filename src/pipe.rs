@@ -8,7 +8,37 @@ use shell::JobKind;
 use status::*;
 use parser::peg::{Pipeline, RedirectFrom};
 
-pub fn execute_pipeline(pipeline: &mut Pipeline) -> i32 {
+/// Executes the pipeline, returning its exit status, along with whether that status was given by
+/// the final command of the pipeline, rather than by a command whose failure caused the commands
+/// following a `&&` to be skipped.
+pub fn execute_pipeline(pipeline: &mut Pipeline) -> (i32, bool) {
+    let mut piped_commands = build_commands(pipeline);
+    pipe(&mut piped_commands)
+}
+
+/// Executes the pipeline as `execute_pipeline` does, but with the given stream of its final
+/// command written to the output, which a builtin that the pipeline is piped into reads from.
+pub fn execute_pipeline_into(pipeline: &mut Pipeline, from: RedirectFrom, output: File) -> (i32, bool) {
+    let mut piped_commands = build_commands(pipeline);
+    if let Some(&mut (ref mut command, _)) = piped_commands.last_mut() {
+        unsafe {
+            match from {
+                RedirectFrom::Both => {
+                    if let Ok(copy) = output.try_clone() {
+                        command.stderr(Stdio::from_raw_fd(copy.into_raw_fd()));
+                    }
+                    command.stdout(Stdio::from_raw_fd(output.into_raw_fd()));
+                },
+                RedirectFrom::Stderr => { command.stderr(Stdio::from_raw_fd(output.into_raw_fd())); },
+                RedirectFrom::Stdout => { command.stdout(Stdio::from_raw_fd(output.into_raw_fd())); },
+            }
+        }
+    }
+    pipe(&mut piped_commands)
+}
+
+/// Builds the commands of the pipeline, with the redirections of the pipeline applied to them.
+fn build_commands(pipeline: &mut Pipeline) -> Vec<(Command, JobKind)> {
     // Generate a list of commands from the given pipeline
     let mut piped_commands: Vec<(Command, JobKind)> = pipeline.jobs
         .drain(..).map(|mut job| (job.build_command(), job.kind)).collect();
@@ -58,27 +88,31 @@ pub fn execute_pipeline(pipeline: &mut Pipeline) -> i32 {
         }
     }
 
-    pipe(&mut piped_commands)
+    piped_commands
 }
 
 /// This function will panic if called with an empty slice
-pub fn pipe(commands: &mut [(Command, JobKind)]) -> i32 {
+pub fn pipe(commands: &mut [(Command, JobKind)]) -> (i32, bool) {
     let mut previous_status = SUCCESS;
     let mut previous_kind = JobKind::And;
+    let mut skipped = false;
     let mut commands = commands.iter_mut();
     while let Some(&mut (ref mut command, kind)) = commands.next() {
         // When an `&&` or `||` operator is utilized, execute commands based on the previous status.
         match previous_kind {
             JobKind::And => if previous_status != SUCCESS {
                 if let JobKind::Or = kind { previous_kind = kind }
+                skipped = true;
                 continue
             },
             JobKind::Or => if previous_status == SUCCESS {
                 if let JobKind::And = kind { previous_kind = kind }
+                skipped = true;
                 continue
             },
             _ => ()
         }
+        skipped = false;
 
         match kind {
             JobKind::Background => {
@@ -169,7 +203,7 @@ pub fn pipe(commands: &mut [(Command, JobKind)]) -> i32 {
             }
         }
     }
-    previous_status
+    (previous_status, !skipped)
 }
 
 fn execute_command(command: &mut Command) -> i32 {
@@ -1,10 +1,11 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::{self, Write};
 
 use variables::Variables;
 use directory_stack::DirectoryStack;
 use parser::assignments::{self, Binding, Operator, Value};
-use parser::{ExpanderFunctions, Index};
+use parser::with_expanders;
 use status::*;
 
 fn print_vars(list: &HashMap<String, String>) {
@@ -42,57 +43,55 @@ fn print_arrays(list: &HashMap<String, Vec<String>>) {
     }
 }
 
-pub fn let_assignment<'a>(original: &'a str, vars: &mut Variables, dir_stack: &DirectoryStack) -> i32 {
-    let binding = {
-        let expanders = ExpanderFunctions {
-            tilde: &|tilde: &str| vars.tilde_expansion(tilde, dir_stack),
-            array: &|array: &str, index: Index| {
-                match vars.get_array(array) {
-                    Some(array) => match index {
-                            Index::All => Some(array.to_owned())
-                    },
-                    None => None
-                }
-            },
-            variable: &|variable: &str, quoted: bool| {
-                if quoted {
-                    vars.get_var(variable)
-                } else {
-                    vars.get_var(variable).map(|x| x.replace("\n", " "))
-                }
-            },
-            command: &|command: &str, quoted: bool| vars.command_expansion(command, quoted),
-        };
-
-        assignments::parse_assignment(original, &expanders)
-    };
+/// Evaluates a `let` expression. If `global` is set, the assignment bypasses any local scopes
+/// and is performed against the global scope instead. If `nounset` is set, the name of the first
+/// undefined variable which the expression references is returned as an error, and nothing is
+/// assigned.
+pub fn let_assignment<'a>(original: &'a str, global: bool, vars: &mut Variables, dir_stack: &DirectoryStack,
+    nounset: bool) -> Result<i32, String>
+{
+    let undefined = RefCell::new(None);
+    let binding = with_expanders(vars, dir_stack, &undefined,
+        |expanders| assignments::parse_assignment(original, expanders));
+    if nounset {
+        if let Some(variable) = undefined.into_inner() { return Err(variable) }
+    }
 
     match binding {
         Binding::InvalidKey(key) => {
             let stderr = io::stderr();
             let _ = writeln!(&mut stderr.lock(), "ion: variable name, '{}', is invalid", key);
-            return FAILURE;
+            return Ok(FAILURE);
+        },
+        Binding::KeyValue(key, Value::String(value)) => if global {
+            vars.set_global_var(&key, &value)
+        } else {
+            vars.set_var(&key, &value)
+        },
+        Binding::KeyValue(key, Value::Array(array)) => if global {
+            vars.set_global_array(&key, array)
+        } else {
+            vars.set_array(&key, array)
         },
-        Binding::KeyValue(key, Value::String(value)) => vars.set_var(&key, &value),
-        Binding::KeyValue(key, Value::Array(array))  => vars.set_array(&key, array),
         Binding::KeyOnly(key) => {
             let stderr = io::stderr();
             let _ = writeln!(&mut stderr.lock(), "ion: please provide value for variable '{}'", key);
-            return FAILURE;
+            return Ok(FAILURE);
         },
         Binding::ListEntries => {
-            print_vars(&vars.variables);
-            print_arrays(&vars.arrays);
+            print_vars(&vars.visible_variables());
+            print_arrays(&vars.visible_arrays());
         },
         Binding::Math(key, operator, Value::String(value)) => {
-            let left = match vars.get_var(&key).and_then(|x| x.parse::<f32>().ok()) {
+            let current = if global { vars.get_global_var(&key) } else { vars.get_var(&key) };
+            let left = match current.and_then(|x| x.parse::<f32>().ok()) {
                 Some(left) => left,
-                None => return FAILURE,
+                None => return Ok(FAILURE),
             };
 
             let right = match value.parse::<f32>().ok() {
                 Some(right) => right,
-                None => return FAILURE
+                None => return Ok(FAILURE)
             };
 
             let result = match operator {
@@ -103,12 +102,33 @@ pub fn let_assignment<'a>(original: &'a str, vars: &mut Variables, dir_stack: &D
                 Operator::Exponent => f32::powf(left, right)
             };
 
-            vars.set_var(&key, &result.to_string());
+            // The result is written back to the scope which the value was read from.
+            if global {
+                vars.set_global_var(&key, &result.to_string());
+            } else {
+                vars.update_var(&key, &result.to_string());
+            }
         },
         Binding::Math(_, _, Value::Array(_)) => {
             unimplemented!();
         }
     }
 
-    SUCCESS
+    Ok(SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_within_a_scope_updates_the_outer_variable() {
+        let mut variables = Variables::default();
+        let dir_stack = DirectoryStack::new().unwrap();
+        variables.set_var("x", "1");
+        variables.new_scope();
+        assert_eq!(Ok(SUCCESS), let_assignment("x += 1", false, &mut variables, &dir_stack, false));
+        variables.pop_scope();
+        assert_eq!(Some("2".to_owned()), variables.get_var("x"));
+    }
 }
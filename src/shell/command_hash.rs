@@ -0,0 +1,101 @@
+//! Remembers where the commands within the directories of `$PATH` are found.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::PathBuf;
+
+use sys::is_executable;
+
+/// A table mapping the name of each command within `$PATH` to the first executable with that
+/// name. The table is built when it is first needed, and is rebuilt after `$PATH` changes, or
+/// after being cleared by `hash -r`, so that the directories are not scanned for every lookup.
+#[derive(Default)]
+pub struct CommandHash {
+    /// The value of `$PATH` that the table was built from, if it has been built.
+    path:     Option<OsString>,
+    commands: HashMap<String, PathBuf>,
+}
+
+impl CommandHash {
+    /// Forgets every command, so that the table will be rebuilt.
+    pub fn clear(&mut self) {
+        self.path = None;
+        self.commands.clear();
+    }
+
+    /// Finds the path of the named command.
+    pub fn get(&mut self, name: &str) -> Option<&PathBuf> {
+        self.update();
+        self.commands.get(name)
+    }
+
+    /// Every command within the table, along with its path.
+    pub fn commands(&mut self) -> &HashMap<String, PathBuf> {
+        self.update();
+        &self.commands
+    }
+
+    fn update(&mut self) {
+        let path = env::var_os("PATH").unwrap_or_else(|| OsString::from("/bin"));
+        if self.path.as_ref() != Some(&path) {
+            self.commands = scan(&path);
+            self.path = Some(path);
+        }
+    }
+}
+
+/// Finds every executable within the directories of the given path, where executables found in
+/// earlier directories take precedence over those found later.
+fn scan(path: &OsStr) -> HashMap<String, PathBuf> {
+    let mut commands = HashMap::new();
+    for directory in env::split_paths(path) {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_)      => continue
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            if let Ok(name) = entry.file_name().into_string() {
+                if !commands.contains_key(&name) && is_executable(entry.path()) {
+                    commands.insert(name, entry.path());
+                }
+            }
+        }
+    }
+    commands
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    fn create(path: &Path, mode: u32) {
+        let file = File::create(path).unwrap();
+        file.set_permissions(fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn scanning_directories() {
+        let root = env::temp_dir().join("ion-command-hash-test");
+        let (first, second) = (root.join("first"), root.join("second"));
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        create(&first.join("one"), 0o755);
+        create(&first.join("data"), 0o644);
+        create(&second.join("one"), 0o755);
+        create(&second.join("two"), 0o755);
+
+        let path = env::join_paths(vec![&first, &root.join("missing"), &second]).unwrap();
+        let commands = scan(&path);
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands.get("one"), Some(&first.join("one")));
+        assert_eq!(commands.get("two"), Some(&second.join("two")));
+    }
+}
@@ -0,0 +1,21 @@
+//! Flags which alter the behavior of the shell, stored within `Shell::flags`.
+
+/// Prints each pipeline, after it has been expanded, before it is executed.
+pub const PRINT_COMMS: u8 = 1;
+/// Exits the shell when a pipeline fails, unless it is a condition or is followed by `&&` or `||`.
+pub const ERR_EXIT: u8 = 2;
+/// Treats the expansion of an undefined variable as an error.
+pub const NO_UNSET: u8 = 4;
+
+/// The options which may be given to `set`, or to the shell on the command line, by their short
+/// flag and long name.
+pub const OPTIONS: [(char, &'static str, u8); 3] = [
+    ('e', "errexit", ERR_EXIT),
+    ('u', "nounset", NO_UNSET),
+    ('x', "xtrace",  PRINT_COMMS),
+];
+
+/// Finds the flag of the option with the given short flag.
+pub fn from_short(short: char) -> Option<u8> {
+    OPTIONS.iter().find(|&&(option, _, _)| option == short).map(|&(_, _, flag)| flag)
+}
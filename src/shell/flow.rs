@@ -1,186 +1,174 @@
-use std::io::{self, Write};
-use std::mem;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
 use status::*;
 use super::Shell;
+use sys;
+use super::flags::{ERR_EXIT, NO_UNSET};
 
-use flow_control::{ElseIf, Function, Statement, collect_loops, collect_if};
-use parser::{ForExpression, StatementSplitter, check_statement};
-use parser::peg::Pipeline;
+use flow_control::{Case, Condition, ElseIf, Function, Statement, pattern_matches};
+use parser::{parse_script, parse_complete_script, ErrorKind, ForExpression, StreamSource, SyntaxError};
+use parser::conditionals::Conditional;
 use super::assignments::let_assignment;
 
 use glob::glob;
 
 pub trait FlowLogic {
+    /// Buffers a line of interactive input, and executes the buffer once it is complete.
     fn on_command(&mut self, command_string: &str);
-    fn execute_toplevel<I>(&mut self, iterator: &mut I, statement: Statement) -> Result<(), &'static str>
-        where I: Iterator<Item = Statement>;
-    fn execute_while(&mut self, expression: Pipeline, statements: Vec<Statement>);
-    fn execute_for(&mut self, variable: &str, values: &[String], statements: Vec<Statement>);
-    fn execute_if(&mut self, expression: Pipeline, success: Vec<Statement>,
-        else_if: Vec<ElseIf>, failure: Vec<Statement>) -> bool;
-    fn execute_statements(&mut self, statements: Vec<Statement>) -> bool;
+    /// Parses the entire script before executing it, so that nothing is executed if the
+    /// script contains a syntax error. The `file` names the script within syntax errors.
+    fn execute_script(&mut self, script: &str, file: &str);
+    fn evaluate(&mut self, expression: &Conditional) -> bool;
+    fn execute_while(&mut self, expression: Conditional, statements: Vec<Statement>) -> Condition;
+    fn execute_for(&mut self, variable: &str, values: &[String], statements: Vec<Statement>) -> Condition;
+    fn execute_if(&mut self, expression: Conditional, success: Vec<Statement>,
+        else_if: Vec<ElseIf>, failure: Vec<Statement>) -> Condition;
+    fn execute_match(&mut self, expression: String, cases: Vec<Case>) -> Condition;
+    fn execute_statements(&mut self, statements: Vec<Statement>) -> Condition;
 }
 
 impl<'a> FlowLogic for Shell<'a> {
     fn on_command(&mut self, command_string: &str) {
-        let mut iterator = StatementSplitter::new(command_string).filter_map(check_statement);
+        // Interactive input is buffered until every block that it opens has been closed, and
+        // then the entire buffer is parsed and executed at once.
+        self.flow_control.buffer.push_str(command_string);
+        self.flow_control.buffer.push('\n');
 
-        // If the value is set to `0`, this means that we don't need to append to an existing
-        // partial statement block in memory, but can read and execute new statements.
-        if self.flow_control.level == 0 {
-            while let Some(statement) = iterator.next() {
-                // Executes all statements that it can, and stores the last remaining partial
-                // statement in memory if needed. We can tell if there is a partial statement
-                // later if the value of `level` is not set to `0`.
-                if let Err(why) = self.execute_toplevel(&mut iterator, statement) {
+        match parse_script(&self.flow_control.buffer, "<stdin>") {
+            Ok(statements) => {
+                self.flow_control.level = 0;
+                self.flow_control.buffer.clear();
+                self.execute_statements(statements);
+            },
+            Err(errors) => {
+                let unterminated = |error: &SyntaxError| match error.kind {
+                    ErrorKind::UnterminatedBlock { .. } => true,
+                    _ => false
+                };
+
+                if errors.iter().all(&unterminated) {
+                    // More input is needed, as each error is a block that has yet to be ended.
+                    self.flow_control.level = errors.len();
+                } else {
                     let stderr = io::stderr();
                     let mut stderr = stderr.lock();
-                    let _ = writeln!(stderr, "{}", why);
+                    for error in errors.iter().filter(|error| !unterminated(*error)) {
+                        let _ = writeln!(stderr, "ion: {}", error);
+                    }
                     self.flow_control.level = 0;
-                    self.flow_control.current_if_mode = 0;
-                    return
-                }
-            }
-        } else {
-            // Appends the newly parsed statements onto the existing statement stored in memory.
-            match self.flow_control.current_statement {
-                Statement::While{ ref mut statements, .. }
-                    | Statement::For { ref mut statements, .. }
-                    | Statement::Function { ref mut statements, .. } =>
-                {
-                    collect_loops(&mut iterator, statements, &mut self.flow_control.level);
-                },
-                Statement::If { ref mut success, ref mut else_if, ref mut failure, .. } => {
-                    self.flow_control.current_if_mode = match collect_if(&mut iterator, success,
-                        else_if, failure, &mut self.flow_control.level,
-                        self.flow_control.current_if_mode) {
-                            Ok(mode) => mode,
-                            Err(why) => {
-                                let stderr = io::stderr();
-                                let mut stderr = stderr.lock();
-                                let _ = writeln!(stderr, "{}", why);
-                                4
-                            }
-                        };
+                    self.flow_control.buffer.clear();
+                    self.previous_status = FAILURE;
                 }
-                _ => ()
-            }
-
-            // If this is true, an error occurred during the if statement
-            if self.flow_control.current_if_mode == 4 {
-                self.flow_control.level = 0;
-                self.flow_control.current_if_mode = 0;
-                self.flow_control.current_statement = Statement::Default;
-                return
             }
+        }
+    }
 
-            // If the level is set to 0, it means that the statement in memory is finished
-            // and thus is ready for execution.
-            if self.flow_control.level == 0 {
-                // Replaces the `current_statement` with a `Default` value to avoid the
-                // need to clone the value, and clearing it at the same time.
-                let mut replacement = Statement::Default;
-                mem::swap(&mut self.flow_control.current_statement, &mut replacement);
-
-                match replacement {
-                    Statement::Let{ expression } => {
-                        self.previous_status = let_assignment(&expression, &mut self.variables, &self.directory_stack);
-                    },
-                    Statement::While { expression, statements } => {
-                        self.execute_while(expression, statements);
-                    },
-                    Statement::For { variable, values, statements } => {
-                        self.execute_for(&variable, &values, statements);
-                    },
-                    Statement::Function { name, args, statements } => {
-                        self.functions.insert(name.clone(), Function {
-                            name:       name,
-                            args:       args,
-                            statements: statements
-                        });
-                    },
-                    Statement::If { expression, success, else_if, failure } => {
-                        self.execute_if(expression, success, else_if, failure);
-                    }
-                    _ => ()
+    fn execute_script(&mut self, script: &str, file: &str) {
+        match parse_complete_script(script, file) {
+            Ok((statements, warnings)) => {
+                let stderr = io::stderr();
+                for warning in warnings {
+                    let _ = writeln!(stderr.lock(), "ion: {}", warning.warning());
                 }
-
-                // Capture any leftover statements.
-                while let Some(statement) = iterator.next() {
-                    if let Err(why) = self.execute_toplevel(&mut iterator, statement) {
-                        let stderr = io::stderr();
-                        let mut stderr = stderr.lock();
-                        let _ = writeln!(stderr, "{}", why);
-                        self.flow_control.level = 0;
-                        self.flow_control.current_if_mode = 0;
-                        return
-                    }
+                self.execute_statements(statements);
+            },
+            Err(errors) => {
+                let stderr = io::stderr();
+                let mut stderr = stderr.lock();
+                for error in errors {
+                    let _ = writeln!(stderr, "ion: {}", error);
                 }
+                self.previous_status = FAILURE;
             }
         }
     }
 
-    fn execute_statements(&mut self, mut statements: Vec<Statement>) -> bool {
-        let mut iterator = statements.drain(..);
-        while let Some(statement) = iterator.next() {
-            match statement {
-                Statement::Let{ expression } => {
-                    self.previous_status = let_assignment(&expression, &mut self.variables, &self.directory_stack);
-                },
-                Statement::While { expression, mut statements } => {
-                    self.flow_control.level += 1;
-                    collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
-                    self.execute_while(expression, statements);
-                },
-                Statement::For { variable, values, mut statements } => {
-                    self.flow_control.level += 1;
-                    collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
-                    self.execute_for(&variable, &values, statements);
-                },
-                Statement::If { expression, mut success, mut else_if, mut failure } => {
-                    self.flow_control.level += 1;
-                    if let Err(why) = collect_if(&mut iterator, &mut success, &mut else_if,
-                        &mut failure, &mut self.flow_control.level, 0) {
-                            let stderr = io::stderr();
-                            let mut stderr = stderr.lock();
-                            let _ = writeln!(stderr, "{}", why);
-                            self.flow_control.level = 0;
-                            self.flow_control.current_if_mode = 0;
-                            return true
-                        }
-                    if self.execute_if(expression, success, else_if, failure) {
-                        return true
+    fn execute_statements(&mut self, statements: Vec<Statement>) -> Condition {
+        for statement in statements {
+            let condition = match statement {
+                Statement::Let { expression, global } => {
+                    let nounset = self.flags & NO_UNSET != 0;
+                    match let_assignment(&expression, global, &mut self.variables, &self.directory_stack, nounset) {
+                        Ok(status)    => self.previous_status = status,
+                        Err(variable) => self.undefined_variable(&variable),
                     }
+                    Condition::NoOp
                 },
-                Statement::Function { name, args, mut statements } => {
-                    self.flow_control.level += 1;
-                    collect_loops(&mut iterator, &mut statements, &mut self.flow_control.level);
+                Statement::While { expression, statements } => self.execute_while(expression, statements),
+                Statement::For { variable, values, statements } => self.execute_for(&variable, &values, statements),
+                Statement::If { expression, success, else_if, failure } => {
+                    self.execute_if(expression, success, else_if, failure)
+                },
+                Statement::Match { expression, cases } => self.execute_match(expression, cases),
+                Statement::Function { name, args, description, statements } => {
                     self.functions.insert(name.clone(), Function {
-                        name:       name,
-                        args:       args,
-                        statements: statements
+                        name:        name,
+                        args:        args,
+                        description: description,
+                        statements:  statements
                     });
+                    Condition::NoOp
                 },
-                Statement::Pipeline(mut pipeline) => { self.run_pipeline(&mut pipeline, false); },
-                Statement::Break => {
-                    return true
-                }
-                _ => {}
+                Statement::Pipeline(mut pipeline) => {
+                    self.run_pipeline(&mut pipeline, false);
+                    Condition::NoOp
+                },
+                Statement::Break(levels)    => Condition::Break(levels),
+                Statement::Continue(levels) => Condition::Continue(levels),
+                _ => Condition::NoOp
+            };
+
+            if condition != Condition::NoOp {
+                return condition;
             }
         }
-        false
+        Condition::NoOp
     }
 
-    fn execute_while(&mut self, expression: Pipeline, statements: Vec<Statement>) {
-        while self.run_pipeline(&mut expression.clone(), false) == Some(SUCCESS) {
+    fn evaluate(&mut self, expression: &Conditional) -> bool {
+        match *expression {
+            Conditional::Pipeline(ref pipeline) => {
+                // The failure of a condition must not cause the shell to exit.
+                let errexit = self.flags & ERR_EXIT;
+                self.flags &= !ERR_EXIT;
+                let status = self.run_pipeline(&mut pipeline.clone(), false);
+                self.flags |= errexit;
+                status == Some(SUCCESS)
+            },
+            Conditional::Comparison(ref left, comparator, ref right) => {
+                let (left, right) = match (self.expand_word(left), self.expand_word(right)) {
+                    (Ok(left), Ok(right)) => (left.join(" "), right.join(" ")),
+                    (Err(variable), _) | (_, Err(variable)) => {
+                        self.undefined_variable(&variable);
+                        return false;
+                    }
+                };
+                let outcome = comparator.compare(&left, &right);
+                self.condition_status(outcome)
+            },
+            Conditional::Not(ref expression) => {
+                let outcome = !self.evaluate(expression);
+                self.condition_status(outcome)
+            },
+            Conditional::And(ref left, ref right) => self.evaluate(left) && self.evaluate(right),
+            Conditional::Or(ref left, ref right) => self.evaluate(left) || self.evaluate(right),
+        }
+    }
+
+    fn execute_while(&mut self, expression: Conditional, statements: Vec<Statement>) -> Condition {
+        while self.evaluate(&expression) {
             // Cloning is needed so the statement can be re-iterated again if needed.
-            if self.execute_statements(statements.clone()) {
-                break
+            match self.execute_statements(statements.clone()) {
+                Condition::NoOp | Condition::Continue(1) => (),
+                condition => return condition.unwind()
             }
         }
+        Condition::NoOp
     }
 
-    fn execute_for(&mut self, variable: &str, values: &[String], statements: Vec<Statement>) {
+    fn execute_for(&mut self, variable: &str, values: &[String], statements: Vec<Statement>) -> Condition {
         fn glob_expand(arg: &str) -> Vec<String> {
             let mut expanded = Vec::new();
             if arg.contains(|chr| chr == '?' || chr == '*' || chr == '[') {
@@ -195,156 +183,199 @@ impl<'a> FlowLogic for Shell<'a> {
             }
         }
 
-        match ForExpression::new(values, &self.directory_stack, &self.variables) {
+        // Executes the loop body with the given value, and then either moves on to the next
+        // value or exits the loop, depending on the condition returned by the body.
+        macro_rules! iterate {
+            ($value:expr) => {{
+                let value = $value;
+                if variable != "_" { self.variables.set_var(variable, &value); }
+                match self.execute_statements(statements.clone()) {
+                    Condition::NoOp | Condition::Continue(1) => (),
+                    condition => return condition.unwind()
+                }
+            }}
+        }
+
+        let nounset = self.flags & NO_UNSET != 0;
+        let expression = match ForExpression::new(values, &self.directory_stack, &self.variables, nounset) {
+            Ok(expression) => expression,
+            Err(variable) => {
+                self.undefined_variable(&variable);
+                return Condition::NoOp;
+            }
+        };
+        match expression {
             ForExpression::Multiple(values) => {
                 for value in values.iter().flat_map(|x| glob_expand(x.as_str())) {
-                    if value != "_" { self.variables.set_var(variable, &value); }
-                    if self.execute_statements(statements.clone()) { break }
+                    iterate!(value);
                 }
             },
             ForExpression::Normal(values) => {
                 for value in values.lines().flat_map(glob_expand) {
-                    if value != "_" { self.variables.set_var(variable, &value); }
-                    if self.execute_statements(statements.clone()) { break }
+                    iterate!(value);
                 }
             },
-            ForExpression::Range(start, end) => {
-                for value in (start..end).map(|x| x.to_string()) {
-                    if value != "_" { self.variables.set_var(variable, &value); }
-                    if self.execute_statements(statements.clone()) { break }
+            ForExpression::Range(range) => {
+                for value in range {
+                    iterate!(value);
                 }
-            }
-        }
-    }
+            },
+            ForExpression::Stream(source, delimiter) => {
+                // Records are read one at a time, so that the loop can begin before the source
+                // has finished writing, and so that the entire stream is never held in memory.
+                let mut producer = None;
+                let reader: Box<BufRead> = match source {
+                    StreamSource::File(path) => match File::open(&path) {
+                        Ok(file) => Box::new(BufReader::new(file)),
+                        Err(why) => {
+                            let stderr = io::stderr();
+                            let _ = writeln!(stderr.lock(), "ion: unable to open {}: {}", path, why);
+                            self.previous_status = FAILURE;
+                            return Condition::NoOp;
+                        }
+                    },
+                    // The command is executed by a fork of the shell, so that it may use the
+                    // functions, aliases, and variables which are defined within the shell.
+                    // Where the shell may not be forked, a new instance of it is spawned instead.
+                    StreamSource::Command(command) => {
+                        let output = match self.subshell(|shell| shell.execute_script(&command, "<command>")) {
+                            Ok((output, pid)) => Ok((Box::new(BufReader::new(output)) as Box<BufRead>, Producer::Fork(pid))),
+                            Err(_) => env::current_exe().and_then(|exe| {
+                                Command::new(exe).arg("-c").arg(&command).stdout(Stdio::piped()).spawn()
+                            }).map(|mut process| {
+                                let stdout = process.stdout.take().expect("stdout of child was not piped");
+                                (Box::new(BufReader::new(stdout)) as Box<BufRead>, Producer::Process(process))
+                            })
+                        };
+                        match output {
+                            Ok((output, process)) => {
+                                producer = Some(process);
+                                output
+                            },
+                            Err(why) => {
+                                let stderr = io::stderr();
+                                let _ = writeln!(stderr.lock(), "ion: unable to execute {}: {}", command, why);
+                                self.previous_status = FAILURE;
+                                return Condition::NoOp;
+                            }
+                        }
+                    }
+                };
 
-    fn execute_if(&mut self, mut expression: Pipeline, success: Vec<Statement>,
-        mut else_if: Vec<ElseIf>, failure: Vec<Statement>) -> bool
-    {
-        match self.run_pipeline(&mut expression, false) {
-            Some(SUCCESS) => self.execute_statements(success),
-            _             => {
-                for mut elseif in else_if.drain(..) {
-                    if self.run_pipeline(&mut elseif.expression, false) == Some(SUCCESS) {
-                        return self.execute_statements(elseif.success);
+                let mut condition = Condition::NoOp;
+                let mut exited = false;
+                for record in reader.split(delimiter) {
+                    let mut record = match record {
+                        Ok(record) => record,
+                        Err(_) => break
+                    };
+                    if delimiter == b'\n' && record.last() == Some(&b'\r') { record.pop(); }
+                    let value = String::from_utf8_lossy(&record).into_owned();
+                    if variable != "_" { self.variables.set_var(variable, &value); }
+                    match self.execute_statements(statements.clone()) {
+                        Condition::NoOp | Condition::Continue(1) => (),
+                        other => {
+                            condition = other.unwind();
+                            exited = true;
+                            break
+                        }
+                    }
+                }
+
+                // The command may still be writing if the loop was exited early. Otherwise, the
+                // loop exits with the status of the command.
+                if let Some(producer) = producer {
+                    if exited {
+                        producer.kill();
+                    } else {
+                        self.previous_status = producer.wait();
                     }
                 }
-                self.execute_statements(failure)
+                return condition;
             }
         }
+        Condition::NoOp
     }
 
-    fn execute_toplevel<I>(&mut self, iterator: &mut I, statement: Statement) -> Result<(), &'static str>
-        where I: Iterator<Item = Statement>
+    fn execute_if(&mut self, expression: Conditional, success: Vec<Statement>,
+        mut else_if: Vec<ElseIf>, failure: Vec<Statement>) -> Condition
     {
-        match statement {
-            // Execute a Let Statement
-            Statement::Let{ expression } => {
-                self.previous_status = let_assignment(&expression, &mut self.variables, &self.directory_stack);
-            },
-            // Collect the statements for the while loop, and if the loop is complete,
-            // execute the while loop with the provided expression.
-            Statement::While { expression, mut statements } => {
-                self.flow_control.level += 1;
-
-                // Collect all of the statements contained within the while block.
-                collect_loops(iterator, &mut statements, &mut self.flow_control.level);
-
-                if self.flow_control.level == 0 {
-                    // All blocks were read, thus we can immediately execute now
-                    self.execute_while(expression, statements);
-                } else {
-                    // Store the partial `Statement::While` to memory
-                    self.flow_control.current_statement = Statement::While {
-                        expression: expression,
-                        statements: statements,
-                    }
+        if self.evaluate(&expression) {
+            self.execute_statements(success)
+        } else {
+            for elseif in else_if.drain(..) {
+                if self.evaluate(&elseif.expression) {
+                    return self.execute_statements(elseif.success);
                 }
-            },
-            // Collect the statements for the for loop, and if the loop is complete,
-            // execute the for loop with the provided expression.
-            Statement::For { variable, values, mut statements } => {
-                self.flow_control.level += 1;
-
-                // Collect all of the statements contained within the while block.
-                collect_loops(iterator, &mut statements, &mut self.flow_control.level);
+            }
+            self.execute_statements(failure)
+        }
+    }
 
-                if self.flow_control.level == 0 {
-                    // All blocks were read, thus we can immediately execute now
-                    self.execute_for(&variable, &values, statements);
-                } else {
-                    // Store the partial `Statement::For` to memory
-                    self.flow_control.current_statement = Statement::For {
-                        variable:   variable,
-                        values:     values,
-                        statements: statements,
+    fn execute_match(&mut self, expression: String, cases: Vec<Case>) -> Condition {
+        let value = match self.expand_word(&expression) {
+            Ok(value) => value.join(" "),
+            Err(variable) => {
+                self.undefined_variable(&variable);
+                return Condition::NoOp;
+            }
+        };
+        for case in cases {
+            // Each alternative is expanded before matching, so that variables may be used
+            // within patterns, and brace expansions provide additional alternatives.
+            let mut is_match = case.value.is_none();
+            for alternative in case.value.iter().flat_map(|pattern| pattern.split('|')) {
+                match self.expand_word(alternative.trim()) {
+                    Ok(patterns) => if patterns.iter().any(|pattern| pattern_matches(pattern, &value)) {
+                        is_match = true;
+                        break;
+                    },
+                    Err(variable) => {
+                        self.undefined_variable(&variable);
+                        return Condition::NoOp;
                     }
                 }
-            },
-            // Collect the statements needed for the `success`, `else_if`, and `failure`
-            // conditions; then execute the if statement if it is complete.
-            Statement::If { expression, mut success, mut else_if, mut failure } => {
-                self.flow_control.level += 1;
+            }
 
-                // Collect all of the success and failure statements within the if condition.
-                // The `mode` value will let us know whether the collector ended while
-                // collecting the success block or the failure block.
-                let mode = collect_if(iterator, &mut success, &mut else_if,
-                    &mut failure, &mut self.flow_control.level, 0)?;
+            if is_match {
+                return self.execute_statements(case.statements);
+            }
+        }
+        Condition::NoOp
+    }
+}
 
-                if self.flow_control.level == 0 {
-                    // All blocks were read, thus we can immediately execute now
-                    self.execute_if(expression, success, else_if, failure);
-                } else {
-                    // Set the mode and partial if statement in memory.
-                    self.flow_control.current_if_mode = mode;
-                    self.flow_control.current_statement = Statement::If {
-                        expression: expression,
-                        success:    success,
-                        else_if:    else_if,
-                        failure:    failure
-                    };
-                }
-            },
-            // Collect the statements needed by the function and add the function to the
-            // list of functions if it is complete.
-            Statement::Function { name, args, mut statements } => {
-                self.flow_control.level += 1;
+/// The process which writes the records of a stream that a for loop iterates over.
+enum Producer {
+    /// A fork of the shell.
+    Fork(u32),
+    /// A new instance of the shell, where the shell may not be forked.
+    Process(Child),
+}
 
-                // The same logic that applies to loops, also applies here.
-                collect_loops(iterator, &mut statements, &mut self.flow_control.level);
+impl Producer {
+    /// Waits for the process to exit, returning its status.
+    fn wait(self) -> i32 {
+        match self {
+            Producer::Fork(pid) => sys::wait(pid).unwrap_or(FAILURE),
+            Producer::Process(mut child) => match child.wait() {
+                Ok(status) => status.code().unwrap_or(TERMINATED),
+                Err(_) => FAILURE
+            }
+        }
+    }
 
-                if self.flow_control.level == 0 {
-                    // All blocks were read, thus we can add it to the list
-                    self.functions.insert(name.clone(), Function {
-                        name:       name,
-                        args:       args,
-                        statements: statements
-                    });
-                } else {
-                    // Store the partial function declaration in memory.
-                    self.flow_control.current_statement = Statement::Function {
-                        name:       name,
-                        args:       args,
-                        statements: statements
-                    }
-                }
+    /// Kills the process, which may still be writing, and waits for it to exit.
+    fn kill(self) {
+        match self {
+            Producer::Fork(pid) => {
+                sys::kill(pid);
+                let _ = sys::wait(pid);
             },
-            // Simply executes a provided pipeline, immediately.
-            Statement::Pipeline(mut pipeline) => { self.run_pipeline(&mut pipeline, false); },
-            // At this level, else and else if keywords are forbidden.
-            Statement::ElseIf{..} | Statement::Else => {
-                let stderr = io::stderr();
-                let mut stderr = stderr.lock();
-                let _ = writeln!(stderr, "ion: syntax error: not an if statement");
-            },
-            // Likewise to else and else if, the end keyword does nothing here.
-            Statement::End => {
-                let stderr = io::stderr();
-                let mut stderr = stderr.lock();
-                let _ = writeln!(stderr, "ion: syntax error: no block to end");
-            },
-            _ => {}
+            Producer::Process(mut child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
         }
-        Ok(())
     }
 }
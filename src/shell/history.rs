@@ -1,35 +1,205 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use glob::Pattern;
 use status::*;
+use sys;
 use super::Shell;
+use super::history_expansion;
+use super::history_record::{self, HistoryRecord, Position, HEADER};
 
 /// Contains all history-related functionality for the `Shell`.
 pub trait ShellHistory {
-    /// Prints the commands contained within the history buffers to standard output.
-    fn print_history(&self, _arguments: &[String]) -> i32;
+    /// Prints or alters the records of the commands contained within the history.
+    ///
+    /// - `history`: prints every command, numbered from the oldest
+    /// - `history N`: prints the last N commands which match the filters
+    /// - `history --long`: prints when and where each command ran, how long it ran, and its status
+    /// - `history --search PATTERN`: only prints the commands which contain the pattern
+    /// - `history --cwd`: only prints the commands which ran within the current directory
+    /// - `history --failed`: only prints the commands which failed
+    /// - `history --session`: only prints the commands which ran within this shell
+    /// - `history --since SPAN`: only prints the commands which ran within the span, such as `1d`
+    /// - `history --delete N`: removes the command with the given number
+    /// - `history --clear`: removes every command
+    fn history(&mut self, arguments: &[String]) -> i32;
+
+    /// Records a command, as it was typed, which was started at the given time from the given
+    /// directory, and has just finished with the previous status.
+    ///
+    /// Commands are left out of the history if `HISTORY_IGNORE_SPACE` is set to 1 and the command
+    /// begins with a space, or if the command matches one of the glob patterns within
+    /// `HISTORY_IGNORE`, which are separated by colons. If `HISTORY_IGNORE_DUPS` is set to 1, a
+    /// command which is the same as the previous command is left out, and if `HISTORY_ERASE_DUPS`
+    /// is set to 1, earlier records of the same command are removed.
+    fn record_history(&mut self, command: &str, start: SystemTime, cwd: PathBuf);
+
+    /// Expands the references to the history within a command which was typed, such as `!!` or
+    /// `^old^new`, unless `HISTORY_EXPANSION` is set to 0. When the command is expanded, it is
+    /// printed before it is run, so that it may be seen.
+    fn expand_history(&self, command: String) -> Result<String, String>;
+
+    /// Reads the records of the history file, if it is enabled.
+    fn load_history(&mut self) -> io::Result<()>;
+
+    /// Writes the records to the history file, if it is enabled, replacing its contents. Records
+    /// which other shells have written to the file since it was last read are kept.
+    fn save_history(&mut self) -> io::Result<()>;
+
+    /// Reads the records which other shells have written to the history file since it was last
+    /// read, if `HISTORY_SHARED` is set to 1, so that their commands may be recalled. This is
+    /// called before each prompt.
+    fn import_history(&mut self) -> io::Result<()>;
 
     /// Sets the history size for the shell context equal to the HISTORY_SIZE shell variable if it
     /// is set otherwise to a default value (1000).
     ///
-    /// If the HISTORY_FILE_ENABLED shell variable is set to 1, then HISTORY_FILE_SIZE is synced
-    /// with the shell context as well. Otherwise, the history file name is set to None in the
-    /// shell context.
+    /// The history file is written by the shell, rather than by the context, so that each record
+    /// retains its details. As such, the history file name is always set to None in the context.
     ///
-    /// This is called in on_command so that the history length and history file state will be
-    /// updated correctly after a command is entered that alters them and just before loading the
-    /// history file so that it will be loaded correctly.
+    /// This is called before each command is recorded, so that the history length will be
+    /// updated correctly after a command is entered that alters it.
     fn set_context_history_from_vars(&mut self);
 }
 
 impl<'a> ShellHistory for Shell<'a> {
-    fn print_history(&self, _arguments: &[String]) -> i32 {
-        let mut buffer = Vec::with_capacity(8*1024);
-        for command in &self.context.history.buffers {
-            let _ = writeln!(buffer, "{}", command);
+    fn history(&mut self, arguments: &[String]) -> i32 {
+        let result = match arguments.get(1).map(|argument| argument.as_str()) {
+            Some("--clear") => {
+                self.records.clear();
+                self.sync_context_history();
+                self.save_history().map_err(|why| format!("failed to write the history file: {}", why))
+            },
+            Some("--delete") => match arguments.get(2).and_then(|number| number.parse::<usize>().ok()) {
+                Some(number) if number >= 1 && number <= self.records.len() => {
+                    self.records.remove(number - 1);
+                    self.sync_context_history();
+                    self.save_history().map_err(|why| format!("failed to write the history file: {}", why))
+                },
+                _ => Err("--delete requires the number of a command within the history".to_owned())
+            },
+            _ => Filter::parse(&arguments[1..]).map(|filter| filter.print(&self.records, self.session))
+        };
+
+        match result {
+            Ok(()) => SUCCESS,
+            Err(why) => {
+                let stderr = io::stderr();
+                let _ = writeln!(stderr.lock(), "ion: history: {}", why);
+                FAILURE
+            }
         }
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
-        let _ = stdout.write_all(&buffer);
-        SUCCESS
+    }
+
+    fn record_history(&mut self, command: &str, start: SystemTime, cwd: PathBuf) {
+        let ignore_space = self.variables.get_var_or_empty("HISTORY_IGNORE_SPACE") == "1";
+        if is_ignored(command, ignore_space, &self.variables.get_var_or_empty("HISTORY_IGNORE")) { return }
+
+        let command = command.trim();
+        let ignore_dups = self.variables.get_var_or_empty("HISTORY_IGNORE_DUPS") == "1";
+        if ignore_dups && self.records.last().map_or(false, |record| record.command == command) { return }
+
+        self.set_context_history_from_vars();
+        let record = HistoryRecord {
+            command:  command.to_owned(),
+            time:     history_record::seconds(start),
+            duration: start.elapsed().map(history_record::milliseconds).unwrap_or(0),
+            status:   self.previous_status,
+            cwd:      cwd,
+            session:  self.session,
+        };
+
+        let erase_dups = self.variables.get_var_or_empty("HISTORY_ERASE_DUPS") == "1";
+        let erased = erase_dups && self.records.iter().any(|earlier| earlier.command == command);
+        let written = if erased {
+            // The earlier records are removed from the history file as well, so it is written again.
+            self.records.retain(|earlier| earlier.command != command);
+            self.records.push(record);
+            self.sync_context_history();
+            self.save_history()
+        } else {
+            if let Err(err) = self.context.history.push(command.into()) {
+                let stderr = io::stderr();
+                let _ = writeln!(stderr.lock(), "ion: {}", err);
+            }
+            let written = self.append_history(&record);
+            self.records.push(record);
+            written
+        };
+        if let Err(err) = written {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: failed to write the history file: {}", err);
+        }
+
+        self.trim_history();
+    }
+
+    fn expand_history(&self, command: String) -> Result<String, String> {
+        if self.variables.get_var_or_empty("HISTORY_EXPANSION") == "0" { return Ok(command) }
+        if !command.contains('!') && !command.trim_left().starts_with('^') { return Ok(command) }
+
+        let history: Vec<String> = self.records.iter().map(|record| record.command.clone()).collect();
+        let expanded = history_expansion::expand(&command, &history)?;
+        if expanded != command { println!("{}", expanded.trim()); }
+        Ok(expanded)
+    }
+
+    fn load_history(&mut self) -> io::Result<()> {
+        let path = match self.history_file() {
+            Some(path) => path,
+            None       => return Ok(())
+        };
+
+        let (mut records, current, position) = history_record::load(&path)?;
+        let max_file_size = self.variables.get_var_or_empty("HISTORY_FILE_SIZE").parse().unwrap_or(1000);
+        let trimmed = records.len() > max_file_size;
+        if trimmed {
+            let excess = records.len() - max_file_size;
+            records.drain(..excess);
+        }
+
+        self.records = records;
+        self.history_position = position;
+        self.set_context_history_from_vars();
+        self.sync_context_history();
+
+        // Files written in the earlier format, or which have grown too large, are written again.
+        if trimmed || !current { self.save_history()?; }
+        Ok(())
+    }
+
+    fn save_history(&mut self) -> io::Result<()> {
+        let path = match self.history_file() {
+            Some(path) => path,
+            None       => return Ok(())
+        };
+        let mut file = OpenOptions::new().read(true).write(true).create(true).open(&path)?;
+        sys::lock_file(&file, true)?;
+        let (records, _) = history_record::read_since(&mut file, self.history_position)?;
+        self.merge_records(records);
+        self.history_position = history_record::rewrite(&mut file, &self.records)?;
+        Ok(())
+    }
+
+    fn import_history(&mut self) -> io::Result<()> {
+        if self.variables.get_var_or_empty("HISTORY_SHARED") != "1" { return Ok(()) }
+        let path = match self.history_file() {
+            Some(path) => path,
+            None       => return Ok(())
+        };
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(ref why) if why.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(why) => return Err(why)
+        };
+        sys::lock_file(&file, false)?;
+        let (records, position) = history_record::read_since(&mut file, self.history_position)?;
+        self.history_position = position;
+        self.merge_records(records);
+        Ok(())
     }
 
     fn set_context_history_from_vars(&mut self) {
@@ -39,18 +209,212 @@ impl<'a> ShellHistory for Shell<'a> {
             .unwrap_or(1000);
 
         self.context.history.set_max_size(max_history_size);
+        self.context.history.set_file_name(None);
+    }
+}
 
-        if self.variables.get_var_or_empty("HISTORY_FILE_ENABLED") == "1" {
-            let file_name = self.variables.get_var("HISTORY_FILE");
-            self.context.history.set_file_name(file_name);
+impl<'a> Shell<'a> {
+    /// The path of the history file, if `HISTORY_FILE_ENABLED` is set to 1.
+    fn history_file(&self) -> Option<PathBuf> {
+        if self.variables.get_var_or_empty("HISTORY_FILE_ENABLED") != "1" { return None }
+        self.variables.get_var("HISTORY_FILE").map(PathBuf::from)
+    }
 
-            let max_history_file_size = self.variables
-                .get_var_or_empty("HISTORY_FILE_SIZE")
-                .parse()
-                .unwrap_or(1000);
-            self.context.history.set_max_file_size(max_history_file_size);
-        } else {
-            self.context.history.set_file_name(None);
+    fn append_history(&mut self, record: &HistoryRecord) -> io::Result<()> {
+        let path = match self.history_file() {
+            Some(path) => path,
+            None       => return Ok(())
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // Other shells may be writing to the file at the same time, so each record is written whole.
+        sys::lock_file(&file, true)?;
+        let length = file.metadata()?.len();
+        let mut contents = if length == 0 { format!("{}\n", HEADER) } else { String::new() };
+        contents.push_str(&record.to_line());
+        contents.push('\n');
+        file.write_all(contents.as_bytes())?;
+
+        // Unless other shells have written to the file since it was last read, reading continues
+        // after this record, so that it is not read again.
+        if length == 0 {
+            self.history_position = Position { generation: 0, offset: contents.len() as u64 };
+        } else if length == self.history_position.offset {
+            self.history_position.offset += contents.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Adds the records which were read from the history file to the end of the history, besides
+    /// those which it already contains. Records which this shell wrote itself are never added
+    /// again, as they may since have been deleted or erased as duplicates.
+    fn merge_records(&mut self, records: Vec<HistoryRecord>) {
+        let records: Vec<HistoryRecord> = {
+            let known: HashSet<&HistoryRecord> = self.records.iter().collect();
+            let session = self.session;
+            records.into_iter().filter(|record| record.session != session && !known.contains(record)).collect()
+        };
+        for record in records {
+            self.context.history.buffers.push_back(record.command.as_str().into());
+            self.records.push(record);
+        }
+        self.trim_history();
+    }
+
+    /// Removes the oldest records once there are more than `HISTORY_SIZE`.
+    fn trim_history(&mut self) {
+        let max_size = self.variables.get_var_or_empty("HISTORY_SIZE").parse().unwrap_or(1000);
+        if self.records.len() > max_size {
+            let excess = self.records.len() - max_size;
+            self.records.drain(..excess);
+        }
+        while self.context.history.buffers.len() > max_size {
+            self.context.history.buffers.pop_front();
+        }
+    }
+
+    /// Replaces the commands within the context, which are recalled by the editor, with those of
+    /// the records.
+    fn sync_context_history(&mut self) {
+        self.context.history.buffers.clear();
+        for record in &self.records {
+            self.context.history.buffers.push_back(record.command.as_str().into());
+        }
+    }
+}
+
+/// Whether the command, as it was typed, is left out of the history, because it begins with a space
+/// while `ignore_space` is set, or because it matches one of the colon-separated glob `patterns`.
+fn is_ignored(command: &str, ignore_space: bool, patterns: &str) -> bool {
+    (ignore_space && command.starts_with(' ')) || patterns.split(':')
+        .filter(|pattern| !pattern.is_empty())
+        .filter_map(|pattern| Pattern::new(pattern).ok())
+        .any(|pattern| pattern.matches(command.trim()))
+}
+
+/// Selects which records are printed by `history`, and how they are printed.
+#[derive(Debug, Default, PartialEq)]
+struct Filter {
+    count:   Option<usize>,
+    long:    bool,
+    search:  Option<String>,
+    cwd:     Option<PathBuf>,
+    failed:  bool,
+    session: bool,
+    /// The number of seconds before now from which records are printed.
+    since:   Option<u64>,
+}
+
+impl Filter {
+    fn parse(arguments: &[String]) -> Result<Filter, String> {
+        let mut filter = Filter::default();
+        let mut arguments = arguments.iter();
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "-l" | "--long" => filter.long = true,
+                "--failed"      => filter.failed = true,
+                "--session"     => filter.session = true,
+                "--cwd" => {
+                    let cwd = env::current_dir().map_err(|why| format!("failed to get the directory: {}", why))?;
+                    filter.cwd = Some(cwd);
+                },
+                "--search" => match arguments.next() {
+                    Some(pattern) => filter.search = Some(pattern.clone()),
+                    None          => return Err("no pattern was given to search for".to_owned())
+                },
+                "--since" => match arguments.next().and_then(|span| history_record::parse_span(span)) {
+                    Some(since) => filter.since = Some(since),
+                    None        => return Err("--since requires a span of time, such as 30m, 12h, or 7d".to_owned())
+                },
+                count => match count.parse::<usize>() {
+                    Ok(count) => filter.count = Some(count),
+                    Err(_)    => return Err(format!("invalid argument: {}", count))
+                }
+            }
         }
+        Ok(filter)
+    }
+
+    fn matches(&self, record: &HistoryRecord, session: u64, now: u64) -> bool {
+        self.search.as_ref().map_or(true, |pattern| record.command.contains(pattern.as_str()))
+            && self.cwd.as_ref().map_or(true, |cwd| record.cwd == *cwd)
+            && (!self.failed || record.status != SUCCESS)
+            && (!self.session || record.session == session)
+            && self.since.map_or(true, |since| record.time != 0 && record.time + since >= now)
+    }
+
+    /// Prints each record which matches, along with its number, which begins from 1 for the
+    /// oldest record. Commands spanning multiple lines have each following line aligned with the
+    /// first.
+    fn print(&self, records: &[HistoryRecord], session: u64) {
+        let now = history_record::seconds(SystemTime::now());
+        let matches: Vec<(usize, &HistoryRecord)> = records.iter().enumerate()
+            .filter(|&(_, record)| self.matches(record, session, now))
+            .collect();
+        let skip = self.count.map_or(0, |count| matches.len().saturating_sub(count));
+
+        let mut buffer = Vec::with_capacity(8*1024);
+        for &(id, record) in &matches[skip..] {
+            if self.long {
+                let (time, duration) = if record.time == 0 {
+                    ("-".to_owned(), "-".to_owned())
+                } else {
+                    (history_record::format_time(record.time), history_record::format_duration(record.duration))
+                };
+                let _ = write!(buffer, "{:>5}  {:19}  {:>7}  {:>4}  {}\n       ", id + 1, time, duration,
+                    record.status, record.cwd.display());
+            } else {
+                let _ = write!(buffer, "{:>5}  ", id + 1);
+            }
+            let _ = writeln!(buffer, "{}", record.command.replace("\n", "\n       "));
+        }
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(&buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_helpers::strings;
+
+    fn record(command: &str, time: u64, status: i32, cwd: &str, session: u64) -> HistoryRecord {
+        HistoryRecord {
+            time: time, status: status, cwd: PathBuf::from(cwd), session: session,
+            ..HistoryRecord::unknown(command.to_owned())
+        }
+    }
+
+    #[test]
+    fn ignoring_commands() {
+        assert!(is_ignored(" export TOKEN=secret", true, ""));
+        assert!(!is_ignored(" ls", false, ""));
+        assert!(is_ignored("curl -H TOKEN=secret", false, "ls:*TOKEN=*"));
+        assert!(is_ignored("ls", false, "ls:*TOKEN=*"));
+        assert!(!is_ignored("ls -l", false, "ls:*TOKEN=*"));
+        assert!(!is_ignored("ls -l", false, "::"));
+    }
+
+    #[test]
+    fn filtering_records() {
+        let filter = Filter::parse(&strings(&["--failed", "--since", "1d", "--search", "kubectl", "10"])).unwrap();
+        assert_eq!(filter.count, Some(10));
+        assert_eq!(filter.since, Some(86400));
+
+        let now = 1_000_000;
+        assert!(filter.matches(&record("kubectl apply", now - 3600, 1, "/", 1), 2, now));
+        assert!(!filter.matches(&record("kubectl apply", now - 3600, 0, "/", 1), 2, now));
+        assert!(!filter.matches(&record("kubectl apply", now - 90_000, 1, "/", 1), 2, now));
+        assert!(!filter.matches(&record("kubectl apply", 0, 1, "/", 1), 2, now));
+        assert!(!filter.matches(&record("cargo build", now, 1, "/", 1), 2, now));
+
+        let filter = Filter { cwd: Some(PathBuf::from("/repo")), session: true, ..Filter::default() };
+        assert!(filter.matches(&record("make", now, 0, "/repo", 2), 2, now));
+        assert!(!filter.matches(&record("make", now, 0, "/repo", 1), 2, now));
+        assert!(!filter.matches(&record("make", now, 0, "/", 2), 2, now));
+
+        assert!(Filter::parse(&strings(&["--since", "soon"])).is_err());
+        assert!(Filter::parse(&strings(&["--search"])).is_err());
+        assert!(Filter::parse(&strings(&["--bogus"])).is_err());
     }
 }
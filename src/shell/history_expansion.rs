@@ -0,0 +1,139 @@
+//! Expands references to the history within a command as it was typed, as other shells do:
+//!
+//! - `!!`: the previous command
+//! - `!$`: the last argument of the previous command
+//! - `!N`: the command with the given number, as printed by the `history` builtin
+//! - `!-N`: the command N commands before this one
+//! - `!PREFIX`: the most recent command beginning with the prefix
+//! - `^OLD^NEW`: the previous command, with the first occurrence of OLD replaced with NEW
+
+/// Expands every reference to the history within the command, where `history` contains the
+/// commands from the oldest to the most recent. References within single quotes, or which are
+/// escaped with a backslash, are left as they are, as is a `!` that is followed by a space, `=`,
+/// `(`, a quote, `;`, `|`, `&`, or the end of the command.
+pub fn expand(command: &str, history: &[String]) -> Result<String, String> {
+    let trimmed = command.trim_left();
+    if trimmed.starts_with('^') { return substitute(&trimmed[1..], history) }
+
+    let mut output = String::with_capacity(command.len());
+    let mut quoted = false;
+    let mut double_quoted = false;
+    let mut escaped = false;
+    let mut chars = command.char_indices().peekable();
+    while let Some((index, character)) = chars.next() {
+        if escaped || character != '!' || quoted {
+            if escaped {
+                escaped = false;
+            } else if character == '\\' && !quoted {
+                escaped = true;
+            } else if character == '\'' && !double_quoted {
+                quoted = !quoted;
+            } else if character == '"' && !quoted {
+                double_quoted = !double_quoted;
+            }
+            output.push(character);
+            continue
+        }
+
+        let next = chars.peek().map(|&(_, next)| next);
+        let event = match next {
+            Some('!') => { chars.next(); previous(history, "!!")?.to_owned() },
+            Some('$') => {
+                chars.next();
+                previous(history, "!$")?.split_whitespace().last().unwrap_or("").to_owned()
+            },
+            Some(next) if next == '=' || next == '(' || ends_reference(next) => {
+                output.push(character);
+                continue
+            },
+            None => {
+                output.push(character);
+                continue
+            },
+            Some(_) => {
+                let rest = &command[index + 1..];
+                let reference = &rest[..rest.find(ends_reference).unwrap_or(rest.len())];
+                let end = index + 1 + reference.len();
+                while chars.peek().map_or(false, |&(position, _)| position < end) { chars.next(); }
+                find(history, reference).ok_or_else(|| format!("!{}: event not found", reference))?.to_owned()
+            }
+        };
+        output.push_str(&event);
+    }
+    Ok(output)
+}
+
+/// Performs the quick substitution `^OLD^NEW^`, given without the first `^`, upon the previous
+/// command. Anything after the final `^` is appended to the command.
+fn substitute(substitution: &str, history: &[String]) -> Result<String, String> {
+    let mut parts = substitution.splitn(3, '^');
+    let old = parts.next().unwrap_or("");
+    let new = parts.next().unwrap_or("");
+    let rest = parts.next().unwrap_or("");
+
+    let previous = previous(history, "^")?;
+    if old.is_empty() || !previous.contains(old) { return Err(format!("^{}: substitution failed", old)) }
+    Ok(format!("{}{}", previous.replacen(old, new, 1), rest))
+}
+
+/// Whether the character ends a reference to a command by its number or prefix.
+fn ends_reference(character: char) -> bool {
+    character.is_whitespace() || [';', '|', '&', '\'', '"'].contains(&character)
+}
+
+fn previous<'a>(history: &'a [String], reference: &str) -> Result<&'a str, String> {
+    history.last().map(|command| command.as_str()).ok_or_else(|| format!("{}: event not found", reference))
+}
+
+/// Finds the command which the reference following a `!` refers to: either a number, counting
+/// from the oldest command, a negative number, counting back from the most recent, or a prefix.
+fn find<'a>(history: &'a [String], reference: &str) -> Option<&'a str> {
+    let index = if reference.starts_with('-') {
+        reference[1..].parse::<usize>().ok()
+            .and_then(|back| if back >= 1 && back <= history.len() { Some(history.len() - back) } else { None })
+    } else if let Ok(number) = reference.parse::<usize>() {
+        if number >= 1 && number <= history.len() { Some(number - 1) } else { None }
+    } else {
+        history.iter().rposition(|command| command.starts_with(reference))
+    };
+    index.map(|index| history[index].as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        ["cd /tmp", "git commit -m wip", "ls -l /var/log"].iter().map(|&c| c.to_owned()).collect()
+    }
+
+    #[test]
+    fn expanding_references() {
+        let history = history();
+        assert_eq!(expand("sudo !!", &history), Ok("sudo ls -l /var/log".to_owned()));
+        assert_eq!(expand("cd !$", &history), Ok("cd /var/log".to_owned()));
+        assert_eq!(expand("!1 && !-1", &history), Ok("cd /tmp && ls -l /var/log".to_owned()));
+        assert_eq!(expand("!git --amend", &history), Ok("git commit -m wip --amend".to_owned()));
+        assert_eq!(expand("!make", &history), Err("!make: event not found".to_owned()));
+        assert_eq!(expand("!9", &history), Err("!9: event not found".to_owned()));
+        assert_eq!(expand("!!", &[]), Err("!!: event not found".to_owned()));
+    }
+
+    #[test]
+    fn leaving_literal_bangs() {
+        let history = history();
+        for command in &["echo '!!'", "echo \\!!", "test $a != $b", "echo hi!", "echo ! (x)", "echo \"hi!\"",
+            "echo hi!;", "echo hi!; ls", "echo hi!| cat", "echo 'a'!'b'"] {
+            assert_eq!(expand(command, &history), Ok(command.to_string()));
+        }
+        assert_eq!(expand("echo \"it's !!\"", &history), Ok("echo \"it's ls -l /var/log\"".to_owned()));
+    }
+
+    #[test]
+    fn substituting_the_previous_command() {
+        let history = history();
+        assert_eq!(expand("^/var/log^/etc", &history), Ok("ls -l /etc".to_owned()));
+        assert_eq!(expand("^-l^-a^ | head", &history), Ok("ls -a /var/log | head".to_owned()));
+        assert_eq!(expand("^nope^x", &history), Err("^nope: substitution failed".to_owned()));
+    }
+}
@@ -0,0 +1,287 @@
+//! The records of the history, which describe how and where each command was run, along with how
+//! they are stored within the history file.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sys;
+
+/// The first line of history files which are written in the current format, followed by a tab and
+/// the generation of the file. Files without it are read as a list of commands, one per line, as
+/// they were written by earlier versions.
+pub const HEADER: &'static str = "#ion-history v1";
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct HistoryRecord {
+    pub command:  String,
+    /// The time at which the command was started, in seconds since the Unix epoch.
+    pub time:     u64,
+    /// How long the command ran for, in milliseconds.
+    pub duration: u64,
+    pub status:   i32,
+    /// The working directory from which the command was run.
+    pub cwd:      PathBuf,
+    /// Identifies the shell which ran the command.
+    pub session:  u64,
+}
+
+impl HistoryRecord {
+    /// A record of a command which was read from a history file that lacks any details.
+    pub fn unknown(command: String) -> HistoryRecord {
+        HistoryRecord { command: command, time: 0, duration: 0, status: 0, cwd: PathBuf::new(), session: 0 }
+    }
+
+    /// Formats the record as a single line of the history file, where the fields are separated by
+    /// tabs, and the command is escaped so that it contains neither tabs nor newlines.
+    pub fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\t{}", self.time, self.duration, self.status, self.session,
+            escape(&self.cwd.to_string_lossy()), escape(&self.command))
+    }
+
+    /// Parses a line of the history file, as it was written by `to_line`.
+    pub fn from_line(line: &str) -> Option<HistoryRecord> {
+        let fields: Vec<&str> = line.splitn(6, '\t').collect();
+        if fields.len() != 6 { return None }
+        match (fields[0].parse(), fields[1].parse(), fields[2].parse(), fields[3].parse()) {
+            (Ok(time), Ok(duration), Ok(status), Ok(session)) => Some(HistoryRecord {
+                command:  unescape(fields[5]),
+                time:     time,
+                duration: duration,
+                status:   status,
+                cwd:      PathBuf::from(unescape(fields[4])),
+                session:  session,
+            }),
+            _ => None
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(character) = chars.next() {
+        if character != '\\' {
+            output.push(character);
+            continue
+        }
+        match chars.next() {
+            Some('t')       => output.push('\t'),
+            Some('n')       => output.push('\n'),
+            Some(character) => output.push(character),
+            None            => output.push('\\'),
+        }
+    }
+    output
+}
+
+/// How much of the history file has been read.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    /// Counts how many times the file has been written again, which is given after its header. As
+    /// records are otherwise only ever appended, the offset remains valid while this is unchanged.
+    pub generation: u64,
+    /// The length of the file which has been read.
+    pub offset: u64,
+}
+
+/// Parses the first line of a history file, giving its generation if it was written in the current
+/// format. Files written before the generation was recorded are the first generation.
+fn parse_header(line: &str) -> Option<u64> {
+    if line == HEADER { return Some(0) }
+    if !line.starts_with(HEADER) || !line[HEADER.len()..].starts_with('\t') { return None }
+    line[HEADER.len() + 1..].parse().ok()
+}
+
+fn read_generation(file: &mut File) -> io::Result<u64> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut header = String::new();
+    BufReader::new(&*file).read_line(&mut header)?;
+    Ok(parse_header(header.trim_right_matches('\n')).unwrap_or(0))
+}
+
+/// Reads every record within the history file, along with whether the file was written in the
+/// current format, and the position at which reading should continue. Lines which cannot be
+/// parsed are skipped.
+pub fn load(path: &Path) -> io::Result<(Vec<HistoryRecord>, bool, Position)> {
+    let mut file = File::open(path)?;
+    sys::lock_file(&file, false)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    let length = contents.len() as u64;
+    let mut lines = contents.lines();
+    let first = match lines.next() {
+        Some(line) => line,
+        None       => return Ok((Vec::new(), true, Position::default()))
+    };
+
+    match parse_header(first) {
+        Some(generation) => {
+            let position = Position { generation: generation, offset: length };
+            Ok((lines.filter_map(HistoryRecord::from_line).collect(), true, position))
+        },
+        None => {
+            let records = Some(first).into_iter().chain(lines)
+                .map(|line| HistoryRecord::unknown(line.to_owned()))
+                .collect();
+            Ok((records, false, Position { generation: 0, offset: length }))
+        }
+    }
+}
+
+/// Reads the records which were appended to the history file, which should already be locked,
+/// after the given position, along with the position at which reading should continue. A line
+/// which has yet to be finished is left to be read later. If another shell has written the file
+/// again since, every record within it is read.
+pub fn read_since(file: &mut File, position: Position) -> io::Result<(Vec<HistoryRecord>, Position)> {
+    let length = file.metadata()?.len();
+    if length == 0 { return Ok((Vec::new(), Position::default())) }
+    let generation = read_generation(file)?;
+    let start = if generation == position.generation && length >= position.offset { position.offset } else { 0 };
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let complete = contents.rfind('\n').map_or(0, |end| end + 1);
+    let records = contents[..complete].lines().filter_map(HistoryRecord::from_line).collect();
+    Ok((records, Position { generation: generation, offset: start + complete as u64 }))
+}
+
+/// Replaces the contents of the history file, which should already be locked, with the records,
+/// as its next generation, and returns the position at the end of the file that was written.
+pub fn rewrite(file: &mut File, records: &[HistoryRecord]) -> io::Result<Position> {
+    let generation = if file.metadata()?.len() == 0 { 1 } else { read_generation(file)? + 1 };
+    let mut contents = format!("{}\t{}\n", HEADER, generation);
+    for record in records {
+        contents.push_str(&record.to_line());
+        contents.push('\n');
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(Position { generation: generation, offset: contents.len() as u64 })
+}
+
+/// The number of seconds since the Unix epoch.
+pub fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0)
+}
+
+pub fn milliseconds(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + duration.subsec_nanos() as u64 / 1_000_000
+}
+
+/// Formats the time, given in seconds since the Unix epoch, as a UTC date and time.
+pub fn format_time(time: u64) -> String {
+    let (days, seconds) = (time / 86400, time % 86400);
+    // Converts the number of days since the epoch into a civil date, where years are considered to
+    // begin in March, so that the leap day falls at the end of the year.
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Formats a duration given in milliseconds, such as `850ms`, `12.3s`, or `4m05s`.
+pub fn format_duration(duration: u64) -> String {
+    if duration < 1000 {
+        format!("{}ms", duration)
+    } else if duration < 60_000 {
+        format!("{}.{}s", duration / 1000, duration % 1000 / 100)
+    } else {
+        format!("{}m{:02}s", duration / 60_000, duration / 1000 % 60)
+    }
+}
+
+/// Parses a span of time given to `history --since`, such as `30m`, `12h`, or `7d`, into seconds.
+pub fn parse_span(span: &str) -> Option<u64> {
+    let multiplier = match span.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 3600,
+        Some('d') => 86400,
+        Some('w') => 604800,
+        _         => return None
+    };
+    span[..span.len() - 1].parse::<u64>().ok().map(|number| number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_records() {
+        let record = HistoryRecord {
+            command:  "for i in 1..3\n    echo '\\t'\tdone\nend".to_owned(),
+            time:     1_500_000_000,
+            duration: 1250,
+            status:   1,
+            cwd:      PathBuf::from("/home/ion/my project"),
+            session:  42,
+        };
+        let line = record.to_line();
+        assert!(!line.contains('\n'));
+        assert_eq!(HistoryRecord::from_line(&line), Some(record));
+        assert_eq!(HistoryRecord::from_line("echo one"), None);
+        assert_eq!(parse_header(HEADER), Some(0));
+        assert_eq!(parse_header("#ion-history v1\t7"), Some(7));
+        assert_eq!(parse_header("#ion-history v12"), None);
+    }
+
+    #[test]
+    fn reading_records_written_by_other_shells() {
+        use std::env;
+        use std::fs::{self, OpenOptions};
+
+        // Test runs may happen at the same time, so each uses a file of its own.
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+        let path = env::temp_dir().join(format!("ion-history-record-test-{}", nanos));
+        let record = |command: &str, session| {
+            HistoryRecord { session: session, ..HistoryRecord::unknown(command.to_owned()) }
+        };
+        let mut file = OpenOptions::new().read(true).write(true).create(true).open(&path).unwrap();
+        let position = rewrite(&mut file, &[record("ls", 1)]).unwrap();
+        assert_eq!(position.generation, 1);
+        assert_eq!(load(&path).unwrap(), (vec![record("ls", 1)], true, position));
+
+        let mut other = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(other, "{}\n{}", record("make", 2).to_line(), record("make install", 2).to_line()).unwrap();
+        let (records, position) = read_since(&mut file, position).unwrap();
+        assert_eq!(records, vec![record("make", 2)]);
+        writeln!(other, "").unwrap();
+        let (records, position) = read_since(&mut file, position).unwrap();
+        assert_eq!(records, vec![record("make install", 2)]);
+
+        // Written again by another shell to a file which is longer than the one which was read.
+        let commands: Vec<HistoryRecord> = (0..10).map(|number| record(&format!("echo {}", number), 2)).collect();
+        let rewritten = rewrite(&mut file, &commands).unwrap();
+        assert!(rewritten.offset > position.offset);
+        assert_eq!(read_since(&mut file, position).unwrap(), (commands, rewritten));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn formatting_times() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_time(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_time(1_500_000_000), "2017-07-14 02:40:00");
+        assert_eq!(format_duration(850), "850ms");
+        assert_eq!(format_duration(12_345), "12.3s");
+        assert_eq!(format_duration(245_000), "4m05s");
+        assert_eq!(parse_span("2d"), Some(172_800));
+        assert_eq!(parse_span("30m"), Some(1800));
+        assert_eq!(parse_span("3y"), None);
+    }
+}
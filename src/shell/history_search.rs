@@ -0,0 +1,274 @@
+//! Searches the history incrementally, as the query is typed after pressing `Ctrl-R` or `Ctrl-S`.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use liner::{Editor, Emacs, EventHandler, KeyMap};
+use termion::event::Key;
+
+use super::suggestion::Hint;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Towards older commands, as with `Ctrl-R`.
+    Backward,
+    /// Towards newer commands, as with `Ctrl-S`.
+    Forward,
+}
+
+/// Finds the index of the nearest command containing the query, beginning with the command at
+/// `from` and moving in the given direction.
+pub fn find(history: &[String], query: &str, from: usize, direction: Direction) -> Option<usize> {
+    match direction {
+        Direction::Backward => {
+            let end = if from < history.len() { from + 1 } else { history.len() };
+            history[..end].iter().rposition(|command| command.contains(query))
+        },
+        Direction::Forward if from < history.len() => {
+            history[from..].iter().position(|command| command.contains(query)).map(|position| from + position)
+        },
+        Direction::Forward => None,
+    }
+}
+
+/// The command which was found by a search, and what is to be done with it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selection {
+    /// The search was accepted with the enter key, so the command is executed.
+    Execute(String),
+    /// The search was accepted with an arrow key, so the command is given back to the editor.
+    Edit(String),
+}
+
+/// A search of the history, which is given each key that is typed until it is either accepted
+/// with the enter key or an arrow key, or is cancelled with `Ctrl-C`, `Ctrl-G`, or escape.
+/// Pressing `Ctrl-R` or `Ctrl-S` again cycles through the older or newer commands which contain
+/// the query.
+#[derive(Debug)]
+pub struct Search {
+    query:     String,
+    direction: Direction,
+    found:     Option<usize>,
+    failed:    bool,
+}
+
+impl Search {
+    pub fn new(direction: Direction) -> Search {
+        Search { query: String::new(), direction: direction, found: None, failed: false }
+    }
+
+    /// Updates the search with the key, returning `None` while the search continues, or else
+    /// whether a command was selected. A search which is cancelled selects nothing.
+    pub fn key(&mut self, history: &[String], key: Key) -> Option<Option<Selection>> {
+        // The search begins at the newest command, or continues from the command that was found.
+        let (direction, next) = match key {
+            Key::Ctrl('r') => (Direction::Backward, true),
+            Key::Ctrl('s') => (Direction::Forward, true),
+            _ => (self.direction, false)
+        };
+        self.direction = direction;
+        let start = |found: Option<usize>| match (found, direction) {
+            (Some(index), Direction::Backward) if next => index.wrapping_sub(1),
+            (Some(index), Direction::Forward) if next => index + 1,
+            (Some(index), _) => index,
+            (None, Direction::Backward) => history.len(),
+            (None, Direction::Forward) => 0,
+        };
+
+        match key {
+            Key::Char('\n') => return Some(self.found.map(|index| Selection::Execute(history[index].clone()))),
+            Key::Left | Key::Right | Key::Up | Key::Down => {
+                return Some(self.found.map(|index| Selection::Edit(history[index].clone())));
+            },
+            Key::Ctrl('c') | Key::Ctrl('g') | Key::Esc => return Some(None),
+            Key::Backspace => {
+                self.query.pop();
+                self.found = find(history, &self.query, start(None), direction);
+            },
+            Key::Char(character) => {
+                self.query.push(character);
+                self.found = find(history, &self.query, start(self.found), direction);
+            },
+            Key::Ctrl('r') | Key::Ctrl('s') => {
+                // The last command remains selected when there are no more to cycle through.
+                let index = start(self.found);
+                if index < history.len() {
+                    if let Some(index) = find(history, &self.query, index, direction) { self.found = Some(index); }
+                }
+            },
+            _ => ()
+        }
+
+        self.failed = self.found.is_none() && !self.query.is_empty();
+        None
+    }
+
+    /// The command which was found, if any.
+    pub fn found<'h>(&self, history: &'h [String]) -> Option<&'h str> {
+        self.found.map(|index| history[index].as_str())
+    }
+
+    /// The character of the command upon which the query begins, where the cursor is placed, as it
+    /// is with other shells.
+    pub fn cursor(&self, command: &str) -> Option<usize> {
+        command.find(&self.query).map(|position| command[..position].chars().count())
+    }
+
+    /// Describes the search, such as ``(reverse-i-search)`kub'``.
+    pub fn status(&self) -> String {
+        let label = match self.direction {
+            Direction::Backward => "reverse-i-search",
+            Direction::Forward  => "i-search",
+        };
+        format!("({}{})`{}'", if self.failed { "failed " } else { "" }, label, self.query)
+    }
+}
+
+/// The Emacs key bindings of liner, with `Ctrl-R` and `Ctrl-S` bound to a search of the history.
+/// While searching, the keys which are typed are given to the search rather than the editor, the
+/// command which was found takes the place of the buffer, and the state of the search is drawn
+/// after it as a hint.
+pub struct SearchKeyMap<'a, W: Write> {
+    keymap: Emacs<'a, W>,
+    hint:   Rc<RefCell<Hint>>,
+    /// The search in progress, along with the buffer from before it began.
+    search: Option<(Search, String)>,
+}
+
+impl<'a, W: Write> SearchKeyMap<'a, W> {
+    pub fn new(keymap: Emacs<'a, W>, hint: Rc<RefCell<Hint>>) -> SearchKeyMap<'a, W> {
+        SearchKeyMap { keymap: keymap, hint: hint, search: None }
+    }
+
+    /// Replaces the buffer with the command, and moves the cursor to the given character, or
+    /// otherwise to the end of the command.
+    fn replace(&mut self, command: &str, cursor: Option<usize>) -> io::Result<()> {
+        let editor = self.keymap.editor_mut();
+        editor.move_cursor_to_end_of_line()?;
+        editor.delete_all_before_cursor()?;
+        editor.insert_str_after_cursor(command)?;
+        if let Some(cursor) = cursor { editor.move_cursor_to(cursor)?; }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> KeyMap<'a, W, SearchKeyMap<'a, W>> for SearchKeyMap<'a, W> {
+    fn handle_key_core(&mut self, key: Key) -> io::Result<()> {
+        self.keymap.handle_key_core(key)
+    }
+
+    fn editor(&self) -> &Editor<'a, W> {
+        self.keymap.editor()
+    }
+
+    fn editor_mut(&mut self) -> &mut Editor<'a, W> {
+        self.keymap.editor_mut()
+    }
+
+    fn handle_key(&mut self, key: Key, handler: &mut EventHandler<W>) -> io::Result<bool> {
+        let (mut search, buffer, begun) = match self.search.take() {
+            Some((search, buffer)) => (search, buffer, false),
+            None => {
+                let direction = match key {
+                    Key::Ctrl('r') => Direction::Backward,
+                    Key::Ctrl('s') => Direction::Forward,
+                    _ => return self.keymap.handle_key(key, handler)
+                };
+                (Search::new(direction), self.editor().current_buffer().to_string(), true)
+            }
+        };
+
+        let history: Vec<String> = self.editor_mut().context().history.buffers.iter()
+            .map(|command| command.to_string())
+            .collect();
+        let selection = if begun { None } else { search.key(&history, key) };
+        match selection {
+            None => {
+                let command = search.found(&history).unwrap_or(&buffer).to_owned();
+                self.hint.borrow_mut().set(&command, &format!("   {}", search.status()));
+                self.replace(&command, search.cursor(&command))?;
+                self.search = Some((search, buffer));
+                Ok(false)
+            },
+            Some(selection) => {
+                self.hint.borrow_mut().clear();
+                match selection {
+                    Some(Selection::Execute(command)) => {
+                        self.replace(&command, None)?;
+                        self.keymap.handle_key(Key::Char('\n'), handler)
+                    },
+                    Some(Selection::Edit(command)) => {
+                        self.replace(&command, None)?;
+                        Ok(false)
+                    },
+                    None => {
+                        self.replace(&buffer, None)?;
+                        Ok(false)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, W: Write> From<SearchKeyMap<'a, W>> for String {
+    fn from(keymap: SearchKeyMap<'a, W>) -> String {
+        String::from(keymap.keymap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        ["kubectl get pods", "ls", "kubectl logs web", "cargo build"].iter().map(|&c| c.to_owned()).collect()
+    }
+
+    /// Gives each key to a new search, returning the selection once the search has ended.
+    fn search(history: &[String], direction: Direction, keys: &[Key]) -> Option<Option<Selection>> {
+        let mut search = Search::new(direction);
+        keys.iter().filter_map(|&key| search.key(history, key)).next()
+    }
+
+    #[test]
+    fn finding_commands() {
+        let history = history();
+        assert_eq!(find(&history, "kubectl", history.len(), Direction::Backward), Some(2));
+        assert_eq!(find(&history, "kubectl", 1, Direction::Backward), Some(0));
+        assert_eq!(find(&history, "kubectl", 2, Direction::Backward), Some(2));
+        assert_eq!(find(&history, "kubectl", 0, Direction::Forward), Some(0));
+        assert_eq!(find(&history, "kubectl", 1, Direction::Forward), Some(2));
+        assert_eq!(find(&history, "kubectl", 3, Direction::Forward), None);
+        assert_eq!(find(&history, "kubectl", history.len(), Direction::Forward), None);
+        assert_eq!(find(&history, "make", history.len(), Direction::Backward), None);
+    }
+
+    #[test]
+    fn accepting_searches() {
+        let history = history();
+        let keys = [Key::Char('l'), Key::Char('s'), Key::Char('\n')];
+        assert_eq!(search(&history, Direction::Backward, &keys), Some(Some(Selection::Execute("ls".to_owned()))));
+
+        let keys = [Key::Char('k'), Key::Ctrl('r'), Key::Right];
+        assert_eq!(search(&history, Direction::Backward, &keys),
+            Some(Some(Selection::Edit("kubectl get pods".to_owned()))));
+
+        let keys = [Key::Char('l'), Key::Ctrl('g'), Key::Char('x')];
+        assert_eq!(search(&history, Direction::Backward, &keys), Some(None));
+    }
+
+    #[test]
+    fn describing_searches() {
+        let history = history();
+        let mut search = Search::new(Direction::Backward);
+        assert_eq!(search.key(&history, Key::Char('l')), None);
+        assert_eq!(search.key(&history, Key::Char('o')), None);
+        assert_eq!(search.found(&history), Some("kubectl logs web"));
+        assert_eq!(search.cursor("kubectl logs web"), Some(8));
+        assert_eq!(search.status(), "(reverse-i-search)`lo'");
+        assert_eq!(search.key(&history, Key::Char('x')), None);
+        assert_eq!(search.status(), "(failed reverse-i-search)`lox'");
+    }
+}
@@ -1,9 +1,11 @@
+use std::path::PathBuf;
 use std::process::Command;
 
 use directory_stack::DirectoryStack;
 use glob::glob;
-use parser::expand_string;
+use parser::{expand_defined, expand_string};
 use parser::peg::RedirectFrom;
+use sys;
 use variables::Variables;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -14,6 +16,8 @@ pub struct Job {
     pub command: String,
     pub args: Vec<String>,
     pub kind: JobKind,
+    /// Where the command was found within `$PATH`, if it was looked up before being executed.
+    pub path: Option<PathBuf>,
 }
 
 impl Job {
@@ -23,34 +27,51 @@ impl Job {
             command: command,
             args: args,
             kind: kind,
+            path: None,
         }
     }
 
     /// Takes the current job's arguments and expands them, one argument at a
-    /// time, returning a new `Job` with the expanded arguments.
-    pub fn expand(&mut self, variables: &Variables, dir_stack: &DirectoryStack) {
+    /// time, returning a new `Job` with the expanded arguments. If `nounset` is
+    /// set, the name of the first undefined variable is returned as an error.
+    pub fn expand(&mut self, variables: &Variables, dir_stack: &DirectoryStack, nounset: bool)
+        -> Result<(), String>
+    {
         let mut expanded: Vec<String> = Vec::with_capacity(self.args.len());
         {
             let mut iterator = self.args.drain(..);
             expanded.push(iterator.next().unwrap());
-            for arg in iterator.flat_map(|argument| expand_string(&argument, variables, dir_stack, false)) {
-                if arg.contains(|chr| chr == '?' || chr == '*' || chr == '[') {
-                    if let Ok(glob) = glob(&arg) {
-                        for path in glob.filter_map(Result::ok) {
-                            expanded.push(path.to_string_lossy().into_owned());
-                            continue
+            for argument in iterator {
+                let arguments = if nounset {
+                    expand_defined(&argument, variables, dir_stack, false)?
+                } else {
+                    expand_string(&argument, variables, dir_stack, false)
+                };
+
+                for arg in arguments {
+                    if arg.contains(|chr| chr == '?' || chr == '*' || chr == '[') {
+                        if let Ok(glob) = glob(&arg) {
+                            for path in glob.filter_map(Result::ok) {
+                                expanded.push(path.to_string_lossy().into_owned());
+                                continue
+                            }
                         }
                     }
+                    expanded.push(arg);
                 }
-                expanded.push(arg);
             }
         }
 
         self.args = expanded;
+        Ok(())
     }
 
     pub fn build_command(&mut self) -> Command {
         let mut command = Command::new(&self.command);
+        if let Some(path) = self.path.take() {
+            // The command keeps the name that it was given as its first argument.
+            sys::execute_at(&mut command, path, &self.args);
+        }
         for arg in self.args.drain(..).skip(1) {
             command.arg(arg);
         }
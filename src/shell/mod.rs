@@ -1,31 +1,56 @@
 mod assignments;
+mod command_hash;
+pub mod flags;
 mod history;
+mod history_expansion;
+mod history_record;
+mod history_search;
 mod job;
 mod flow;
+mod options;
+mod suggestion;
 
 pub use self::history::ShellHistory;
 pub use self::job::{Job, JobKind};
 pub use self::flow::FlowLogic;
+pub use self::options::ShellOptions;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io::{self, Read, Stdout, Write};
 use std::env;
 use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 use std::process;
-use std::time::SystemTime;
+use std::rc::Rc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use liner::{Context, CursorPosition, Event, EventKind, FilenameCompleter, BasicCompleter};
+use liner::{ColorClosure, Context, Editor, Emacs, Event, EventHandler, EventKind};
+use termion;
+use termion::event::Key;
+use termion::raw::{IntoRawMode, RawTerminal};
 
 use builtins::*;
-use completer::MultiCompleter;
+use completer::{self, ArgumentKind, Context as CompletionContext, Definition as CompletionDefinition};
+use completer::{Matching, RankedCompleter, Source, TokenCompleter};
+use highlighter;
 use directory_stack::DirectoryStack;
 use flow_control::{FlowControl, Function, Statement};
 use variables::Variables;
 use status::*;
-use pipe::execute_pipeline;
-use parser::{expand_string, StatementSplitter, check_statement, QuoteTerminator};
-use parser::peg::Pipeline;
+use pipe::{execute_pipeline, execute_pipeline_into};
+use parser::{expand_defined, expand_string, incomplete_depth, parse_script, parse_complete_script, StatementSplitter,
+    check_statement, QuoteTerminator};
+use parser::peg::{Pipeline, RedirectFrom};
+use sys;
+use self::command_hash::CommandHash;
+use self::history_record::{HistoryRecord, Position};
+use self::history_search::SearchKeyMap;
+use self::suggestion::Hint;
+use self::flags::*;
+use self::options::USAGE;
 
 /// This struct will contain all of the data structures related to this
 /// instance of the shell.
@@ -35,8 +60,25 @@ pub struct Shell<'a> {
     pub variables: Variables,
     flow_control: FlowControl,
     pub directory_stack: DirectoryStack,
-    functions: HashMap<String, Function>,
+    pub functions: HashMap<String, Function>,
+    /// Definitions of how the arguments of commands are completed, keyed by the command and its
+    /// subcommands, as given to the `complete` builtin.
+    pub completions: HashMap<String, CompletionDefinition>,
+    /// The commands found within `$PATH`, which are used for both execution and completion.
+    pub command_hash: CommandHash,
+    /// The records of the commands within the history, including when and where they were run.
+    records: Vec<HistoryRecord>,
+    /// Identifies the commands of this shell within the history, by the time at which it started.
+    session: u64,
+    /// How much of the history file has been read, so that only the records which other shells
+    /// write after it are read before the next prompt.
+    history_position: Position,
     pub previous_status: i32,
+    /// Options that alter the behavior of the shell, as defined within the `flags` module.
+    pub flags: u8,
+    /// The input of the builtin which is running, when it was redirected from a file or piped
+    /// from the commands before it, rather than read from the standard input of the shell.
+    pub stdin: Option<File>,
 }
 
 impl<'a> Shell<'a> {
@@ -49,87 +91,137 @@ impl<'a> Shell<'a> {
             flow_control: FlowControl::default(),
             directory_stack: DirectoryStack::new().expect(""),
             functions: HashMap::default(),
+            completions: HashMap::default(),
+            command_hash: CommandHash::default(),
+            records: Vec::new(),
+            session: SystemTime::now().duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs() * 1_000_000 + since.subsec_nanos() as u64 / 1000)
+                .unwrap_or(0),
+            history_position: Position::default(),
             previous_status: 0,
+            flags: 0,
+            stdin: None,
         }
     }
     fn readln(&mut self) -> Option<String> {
+        if let Err(why) = self.import_history() {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: failed to read the history file: {}", why);
+        }
         let prompt = self.prompt();
-        let funcs = &self.functions;
-        let vars = &self.variables;
         let builtins = self.builtins;
 
-        // Collects the current list of values from history for completion.
-        let history = &self.context.history.buffers.iter()
-            // Map each underlying `liner::Buffer` into a `String`.
-            .map(|x| x.chars().cloned().collect::<String>())
-            // Collect each result into a vector to avoid borrowing issues.
-            .collect::<Vec<String>>();
-
-        let line = self.context.read_line(prompt, &mut move |Event { editor, kind }| {
-            if let EventKind::BeforeComplete = kind {
-                let (words, pos) = editor.get_words_and_cursor_position();
-
-                let filename = match pos {
-                    CursorPosition::InWord(index) => index > 0,
-                    CursorPosition::InSpace(Some(_), _) => true,
-                    CursorPosition::InSpace(None, _) => false,
-                    CursorPosition::OnWordLeftEdge(index) => index >= 1,
-                    CursorPosition::OnWordRightEdge(index) => {
-                        index >= 1 && !words.into_iter().nth(index).map(|(start, end)| {
-                            let buf = editor.current_buffer();
-                            buf.range(start, end).trim().starts_with('$')
-                        }).unwrap_or(false)
-                    }
-                };
+        // The context is held apart from the shell while reading, so that completions may run functions.
+        let mut context = mem::replace(&mut self.context, Context::new());
+        // A block which has yet to be ended is given back to the editor along with a new line, so
+        // that the entire block may be edited before it is executed.
+        let mut block = String::new();
+        // The suggestion, or the state of a history search, which is drawn after the buffer.
+        let hint = Rc::new(RefCell::new(Hint::default()));
+        let mut line;
+        loop {
+            let colour = self.colour(hint.clone());
+            line = read_line(&mut context, prompt.clone(), block, colour, hint.clone(), &mut |Event { editor, kind }| {
+                match kind {
+                    EventKind::BeforeKey(key) => {
+                        // The suggestion is erased before the line is finished, so that it is not left behind.
+                        if key == Key::Char('\n') && hint.borrow_mut().clear() {
+                            let _ = editor.display();
+                        }
 
-                if filename {
-                    if let Ok(current_dir) = env::current_dir() {
-                        if let Some(url) = current_dir.to_str() {
-                            let completer = FilenameCompleter::new(Some(url));
-                            mem::replace(&mut editor.context().completer, Some(Box::new(completer)));
+                        // Moving past the end of the line accepts the suggestion.
+                        let accept = match key {
+                            Key::Right | Key::End | Key::Ctrl('f') => true,
+                            _ => false
+                        };
+                        if accept {
+                            let buffer = editor.current_buffer().to_string();
+                            if let Some(rest) = self.suggestion(&buffer, editor.cursor()) {
+                                let _ = editor.insert_str_after_cursor(&rest);
+                            }
                         }
+                    },
+                    EventKind::AfterKey(_) => {
+                        // The line is drawn again whenever the suggestion changes.
+                        let buffer = editor.current_buffer().to_string();
+                        let suggestion = self.suggestion(&buffer, editor.cursor()).unwrap_or_default();
+                        let changed = hint.borrow_mut().set(&buffer, &suggestion);
+                        if changed { let _ = editor.display(); }
+                    },
+                    EventKind::BeforeComplete => {
+                        // Parses the text before the cursor to determine what may be completed.
+                        let line = editor.current_buffer().range(0, editor.cursor());
+                        let (context, token) = completer::context(&line);
+
+                        let sources = match context {
+                            CompletionContext::Command => {
+                                // Creates a list of definitions from the shell environment that will be used
+                                // in the creation of a custom completer.
+                                let words = builtins.iter()
+                                    // Add built-in commands to the completer's definitions.
+                                    .map(|(&s, _)| String::from(s))
+                                    // Add the commands found within the directories of **$PATH**.
+                                    .chain(self.command_hash.commands().keys().cloned())
+                                    // Add the aliases to the completer's definitions.
+                                    .chain(self.variables.aliases.keys().cloned())
+                                    // Add the list of available functions to the completer's definitions.
+                                    .chain(self.functions.keys().cloned())
+                                    .collect();
+
+                                // Commands may also be given as a path, such as `./script.ion`.
+                                if token.contains('/') {
+                                    vec![Source::Paths { directories: false }, Source::Words(words)]
+                                } else {
+                                    vec![Source::Words(words)]
+                                }
+                            },
+                            CompletionContext::Argument(words) => match completer::find_definition(&self.completions, &words) {
+                                Some(definition) => {
+                                    let output = match definition.arguments {
+                                        ArgumentKind::Function(ref name) => self.function_output(name, &words, token),
+                                        _ => Vec::new()
+                                    };
+                                    definition.sources(token, output)
+                                },
+                                None => vec![Source::Paths { directories: false }]
+                            },
+                            CompletionContext::Redirection => vec![Source::Paths { directories: false }],
+                            CompletionContext::Directory => vec![Source::Paths { directories: true }],
+                            CompletionContext::Variable  => vec![Source::Words(self.variables.get_vars())],
+                            CompletionContext::Array     => vec![Source::Words(self.variables.get_arrays())],
+                            CompletionContext::User      => vec![Source::Words(completer::user_names())],
+                            CompletionContext::Literal   => Vec::new(),
+                        };
+
+                        // Candidates only need to begin with the token, unless **$COMPLETION_MATCHING** says otherwise.
+                        let matching = Matching::parse(&self.variables.get_var_or_empty("COMPLETION_MATCHING"));
+                        let completions = RankedCompleter::new(sources, matching);
+
+                        // Replace the shell's current completer with one for the token at the cursor.
+                        let completer = TokenCompleter::new(token.to_owned(), Box::new(completions));
+                        mem::replace(&mut editor.context().completer, Some(Box::new(completer)));
                     }
-                } else {
-                    // Creates completers containing definitions from all directories listed
-                    // in the environment's **$PATH** variable.
-                    let file_completers = match env::var("PATH") {
-                        Ok(val) => {
-                            if cfg!(unix) {
-                                // UNIX systems separate paths with the `:` character.
-                                val.split(':').map(|x| FilenameCompleter::new(Some(x))).collect::<Vec<_>>()
-                            } else {
-                                // Redox and Windows use the `;` character to separate paths
-                                val.split(';').map(|x| FilenameCompleter::new(Some(x))).collect::<Vec<_>>()
-                            }
-                        },
-                        Err(_) => vec![FilenameCompleter::new(Some("/bin/"))],
-                    };
-
-                    // Creates a list of definitions from the shell environment that will be used
-                    // in the creation of a custom completer.
-                    let words = builtins.iter()
-                        // Add built-in commands to the completer's definitions.
-                        .map(|(&s, _)| String::from(s))
-                        // Add the history list to the completer's definitions.
-                        .chain(history.iter().cloned())
-                        // Add the aliases to the completer's definitions.
-                        .chain(vars.aliases.keys().cloned())
-                        // Add the list of available functions to the completer's definitions.
-                        .chain(funcs.keys().cloned())
-                        // Add the list of available variables to the completer's definitions.
-                        .chain(vars.get_vars().into_iter().map(|s| format!("${}", s)))
-                        .collect();
-
-                    // Initialize a new completer from the definitions collected.
-                    let custom_completer = BasicCompleter::new(words);
-                    // Merge the collected definitions with the file path definitions.
-                    let completer = MultiCompleter::new(file_completers, custom_completer);
-
-                    // Replace the shell's current completer with the newly-created completer.
-                    mem::replace(&mut editor.context().completer, Some(Box::new(completer)));
                 }
+            });
+
+            match line {
+                Ok(ref command) => match incomplete_depth(&format!("{}{}", self.flow_control.buffer, command)) {
+                    Some(depth) => {
+                        // The lines of the block are drawn again by the editor, so they are erased.
+                        let stdout = io::stdout();
+                        let mut stdout = stdout.lock();
+                        let rows = command.split('\n').count() as u16;
+                        let _ = write!(stdout, "{}\r{}", termion::cursor::Up(rows), termion::clear::AfterCursor);
+                        let _ = stdout.flush();
+                        block = format!("{}\n{}", command, "    ".repeat(depth));
+                    },
+                    None => break
+                },
+                Err(_) => break
             }
-        });
+        }
+
+        self.context = context;
 
         match line {
             Ok(line) => Some(line),
@@ -142,91 +234,197 @@ impl<'a> Shell<'a> {
         }
     }
 
-    pub fn terminate_quotes(&mut self, command: String) -> String {
+    /// Creates the closure with which the editor colours the buffer. The syntax is highlighted, where
+    /// commands are known if they are builtins, functions, aliases, or executables, and the hint is
+    /// drawn after the buffer.
+    fn colour(&self, hint: Rc<RefCell<Hint>>) -> ColorClosure {
+        let commands: HashSet<String> = self.builtins.keys()
+            .map(|&name| name.to_owned())
+            .chain(self.functions.keys().cloned())
+            .chain(self.variables.aliases.keys().cloned())
+            .chain(self.command_hash.commands().keys().cloned())
+            .collect();
+        Box::new(move |line: &str| {
+            let styles = highlighter::highlight(line, |command| {
+                if command.contains('/') { sys::is_executable(command) } else { commands.contains(command) }
+            });
+            let mut output = highlighter::render(line, &styles);
+            if let Some(hint) = hint.borrow().render(line) { output.push_str(&hint); }
+            output
+        })
+    }
+
+    /// Suggests the remainder of a command from the history while the cursor is at the end of the buffer.
+    fn suggestion(&self, buffer: &str, cursor: usize) -> Option<String> {
+        if cursor != buffer.chars().count() { return None }
+        let cwd = env::current_dir().unwrap_or_default();
+        suggestion::suggest(buffer, &self.records, &cwd)
+    }
+
+    /// Runs the function which generates the arguments of a command for completion, returning each
+    /// line that it prints. The function is run within a fork of the shell, so that it cannot alter
+    /// the shell, and is given the words of the command, followed by the word being completed, as
+    /// its arguments and as `@args`.
+    fn function_output(&mut self, name: &str, words: &[String], token: &str) -> Vec<String> {
+        let function = match self.functions.get(name).cloned() {
+            Some(function) => function,
+            None           => return Vec::new()
+        };
+
+        let mut args = words.to_vec();
+        args.push(token.to_owned());
+        let output = self.subshell(move |shell| {
+            for (name, value) in function.args.iter().zip(args.iter().skip(1)) {
+                shell.variables.set_var(name, value);
+            }
+            shell.variables.set_array("args", args);
+            shell.execute_statements(function.statements);
+        }).and_then(|(mut output, pid)| sys::read_until_exit(&mut output, pid));
+
+        output.map(|(output, _)| String::from_utf8_lossy(&output).lines().map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// Reads lines until the given command is no longer within quotes, or does not end with a
+    /// backslash. If the input ends first, `None` is returned.
+    pub fn terminate_quotes(&mut self, command: String) -> Option<String> {
         let mut buffer = QuoteTerminator::new(command);
         self.flow_control.level += 1;
         while !buffer.is_terminated() {
-            loop {
-                if let Some(command) = self.readln() {
-                    buffer.append(command);
-                    break
+            match self.readln() {
+                Some(command) => buffer.append(command),
+                None => {
+                    self.flow_control.level -= 1;
+                    return None
                 }
             }
         }
         self.flow_control.level -= 1;
-        buffer.consume()
+        Some(buffer.consume())
     }
 
     pub fn execute(&mut self) {
-        let mut dash_c = false;
-        for arg in env::args().skip(1) {
-            if arg == "-c" {
-                dash_c = true;
-            } else {
-                if dash_c {
-                    self.on_command(&arg);
-                } else {
-                    match File::open(&arg) {
-                        Ok(mut file) => {
-                            let capacity = file.metadata().ok().map_or(0, |x| x.len());
-                            let mut command_list = String::with_capacity(capacity as usize);
-                            match file.read_to_string(&mut command_list) {
-                                Ok(_) => {
-                                    let mut lines = command_list.lines().map(|x| x.to_owned());
-                                    while let Some(command) = lines.next() {
-                                        let mut buffer = QuoteTerminator::new(command);
-                                        while !buffer.is_terminated() {
-                                            loop {
-                                                if let Some(command) = lines.next() {
-                                                    buffer.append(command);
-                                                    break
-                                                }
-                                            }
-                                        }
-                                        self.on_command(&buffer.consume());
-                                    }
-                                },
-                                Err(err) => {
-                                    let stderr = io::stderr();
-                                    let mut stderr = stderr.lock();
-                                    let _ = writeln!(stderr, "ion: failed to read {}: {}", arg, err);
-                                }
-                            }
-                        },
-                        Err(err) => {
-                            let stderr = io::stderr();
-                            let mut stderr = stderr.lock();
-                            let _ = writeln!(stderr, "ion: failed to open {}: {}", arg, err);
-                        }
-                    }
-                }
+        let options = match ShellOptions::parse(env::args().skip(1)) {
+            Ok(options) => options,
+            Err(why) => {
+                let stderr = io::stderr();
+                let _ = writeln!(stderr.lock(), "ion: {}\n\n{}", why, USAGE);
+                process::exit(FAILURE);
+            }
+        };
+
+        if options.help {
+            println!("{}", USAGE);
+            process::exit(SUCCESS);
+        } else if options.version {
+            println!("ion {}", env!("CARGO_PKG_VERSION"));
+            process::exit(SUCCESS);
+        }
 
-                // Exit with the previous command's exit status.
-                process::exit(self.previous_status);
+        self.flags |= options.flags;
+
+        // Supplies the arguments that follow the script to the script.
+        let name = options.script.clone().unwrap_or_else(|| "ion".to_owned());
+        self.variables.set_var("0", &name);
+        for (id, arg) in options.args.iter().enumerate() {
+            self.variables.set_var(&(id + 1).to_string(), arg);
+        }
+        self.variables.set_array("args", options.args.clone());
+
+        // The script is read from the standard input if it was given as `-`, or if no script
+        // was given and the standard input is not a terminal.
+        let script = match (options.command, options.script) {
+            (Some(command), _) => Some((Some(command), "<command>".to_owned())),
+            (None, Some(ref path)) if path == "-" => Some((read_stdin(), "<stdin>".to_owned())),
+            (None, Some(path)) => Some((read_script(&path), path)),
+            (None, None) if !options.interactive && !sys::isatty(sys::STDIN_FILENO) => {
+                Some((read_stdin(), "<stdin>".to_owned()))
+            },
+            // There is nothing to check when the shell would otherwise be interactive.
+            (None, None) if options.noexec => {
+                let stderr = io::stderr();
+                let _ = writeln!(stderr.lock(), "ion: -n requires a script or a command\n\n{}", USAGE);
+                process::exit(FAILURE);
+            },
+            (None, None) => None
+        };
+
+        if let Some((script, file)) = script {
+            match script.map(|script| parse_complete_script(&script, &file)) {
+                Some(Ok((statements, warnings))) => {
+                    let stderr = io::stderr();
+                    for warning in warnings {
+                        let _ = writeln!(stderr.lock(), "ion: {}", warning.warning());
+                    }
+                    if options.parse_tree {
+                        println!("{:#?}", statements);
+                    }
+                    if !options.noexec {
+                        self.execute_statements(statements);
+                    }
+                },
+                Some(Err(errors)) => {
+                    let stderr = io::stderr();
+                    let mut stderr = stderr.lock();
+                    for error in errors {
+                        let _ = writeln!(stderr, "ion: {}", error);
+                    }
+                    self.previous_status = FAILURE;
+                },
+                None => self.previous_status = FAILURE
             }
+
+            // Exit with the previous command's exit status.
+            process::exit(self.previous_status);
         }
 
         while let Some(command) = self.readln() {
             if ! command.is_empty() {
-                let command = self.terminate_quotes(command);
-                let command = command.trim();
+                let typed = match self.terminate_quotes(command) {
+                    Some(command) => command,
+                    None => {
+                        let stderr = io::stderr();
+                        let _ = writeln!(stderr.lock(), "ion: syntax error: unterminated quote at end of input");
+                        self.previous_status = FAILURE;
+                        break
+                    }
+                };
+                let typed = match self.expand_history(typed) {
+                    Ok(typed) => typed,
+                    Err(why) => {
+                        let stderr = io::stderr();
+                        let _ = writeln!(stderr.lock(), "ion: {}", why);
+                        self.previous_status = FAILURE;
+                        continue
+                    }
+                };
+                let command = typed.trim();
+                let start = SystemTime::now();
+                let cwd = env::current_dir().unwrap_or_default();
 
                 // Parse and potentially execute the command.
                 self.on_command(command);
 
-                // Mark the command in the context history if it was a success.
+                // Mark the command in the history if it was a success.
                 if self.previous_status != NO_SUCH_COMMAND || self.flow_control.level > 0 {
-                    self.set_context_history_from_vars();
-                    if let Err(err) = self.context.history.push(command.into()) {
-                        let stderr = io::stderr();
-                        let mut stderr = stderr.lock();
-                        let _ = writeln!(stderr, "ion: {}", err);
-                    }
+                    self.record_history(&typed, start, cwd);
                 }
             }
             self.update_variables();
         }
 
+        // Blocks which were still open when the input ended are reported as errors.
+        if !self.flow_control.buffer.is_empty() {
+            if let Err(errors) = parse_script(&self.flow_control.buffer, "<stdin>") {
+                let stderr = io::stderr();
+                let mut stderr = stderr.lock();
+                for error in errors {
+                    let _ = writeln!(stderr, "ion: {}", error);
+                }
+                self.previous_status = FAILURE;
+            }
+        }
+
         // Exit with the previous command's exit status.
         process::exit(self.previous_status);
     }
@@ -264,9 +462,7 @@ impl<'a> Shell<'a> {
                     let mut stderr = stderr.lock();
                     let _ = writeln!(stderr, "ion: {}: failed to read {:?}", message, source_file);
                 } else {
-                    for command in command_list.lines() {
-                        self.on_command(command);
-                    }
+                    self.execute_script(&command_list, &source_file.to_string_lossy());
                 }
             }
         });
@@ -281,17 +477,107 @@ impl<'a> Shell<'a> {
         }
     }
 
+    /// Expands the word in the same manner as `expand_string`, but while `set -u` is enabled,
+    /// fails with the name of the first variable which it references that has not been defined.
+    fn expand_word(&self, word: &str) -> Result<Vec<String>, String> {
+        if self.flags & NO_UNSET != 0 {
+            expand_defined(word, &self.variables, &self.directory_stack, false)
+        } else {
+            Ok(expand_string(word, &self.variables, &self.directory_stack, false))
+        }
+    }
+
+    /// Reports that a statement referenced an undefined variable while `set -u` is enabled, and
+    /// fails the statement, which exits the shell if `set -e` is enabled as well.
+    fn undefined_variable(&mut self, variable: &str) {
+        let stderr = io::stderr();
+        let _ = writeln!(stderr.lock(), "ion: {}: undefined variable", variable);
+        self.variables.set_global_var("?", &FAILURE.to_string());
+        self.previous_status = FAILURE;
+        if self.flags & ERR_EXIT != 0 { process::exit(FAILURE); }
+    }
+
+    /// Sets `$?` to the outcome of a condition which was evaluated within the shell, such as a
+    /// comparison, and returns the outcome.
+    fn condition_status(&mut self, outcome: bool) -> bool {
+        let status = if outcome { SUCCESS } else { FAILURE };
+        self.variables.set_global_var("?", &status.to_string());
+        self.previous_status = status;
+        outcome
+    }
+
+    /// Commands found within `$PATH` are executed from where they were found, rather than having
+    /// every directory searched again. Should a command have since been removed from there, the
+    /// directories are searched again.
+    fn locate_commands(&mut self, pipeline: &mut Pipeline) {
+        for job in &mut pipeline.jobs {
+            let mut path = self.command_hash.get(&job.command).cloned();
+            if path.as_ref().map_or(false, |path| !path.exists()) {
+                self.command_hash.clear();
+                path = self.command_hash.get(&job.command).cloned();
+            }
+            job.path = path;
+        }
+    }
+
+    /// Executes the builtin which ends the pipeline within the shell, while the commands before it
+    /// are executed alongside it, with their output written to the pipe that the builtin reads from.
+    fn pipe_into_builtin(&mut self, pipeline: &mut Pipeline, from: RedirectFrom, reader: File, writer: File) -> i32 {
+        let builtin = pipeline.jobs.pop().expect("pipeline ends with a builtin");
+        if let Some(job) = pipeline.jobs.last_mut() { job.kind = JobKind::Last; }
+        self.locate_commands(pipeline);
 
+        let mut commands = Pipeline::new(pipeline.jobs.drain(..).collect(), pipeline.stdin.take(), None);
+        let commands = thread::spawn(move || execute_pipeline_into(&mut commands, from, writer));
+
+        let builtins = self.builtins;
+        self.stdin = Some(reader);
+        let status = (*builtins[builtin.command.as_str()].main)(builtin.args.as_slice(), self);
+        // Dropping the reader ends any command which is still writing to the pipe.
+        self.stdin = None;
+        let _ = commands.join();
+        status
+    }
+
+    /// Runs the action within a fork of the shell, so that the functions, aliases, and variables
+    /// of the shell are available to it, without the action being able to alter them. The output
+    /// of the fork is read from the returned file, and its process ID should be waited for.
+    fn subshell<F: FnOnce(&mut Shell<'a>)>(&mut self, action: F) -> io::Result<(File, u32)> {
+        let (reader, writer) = sys::pipe()?;
+        match sys::fork_into(&writer)? {
+            0 => {
+                drop(reader);
+                drop(writer);
+                // The fork must never return to the caller, even should the action panic.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| action(self)));
+                let status = match result {
+                    Ok(()) => self.previous_status,
+                    Err(_) => FAILURE
+                };
+                let _ = io::stdout().flush();
+                process::exit(status);
+            },
+            pid => Ok((reader, pid))
+        }
+    }
 
     /// Executes a pipeline and returns the final exit status of the pipeline.
     /// To avoid infinite recursion when using aliases, the noalias boolean will be set the true
     /// if an alias branch was executed.
     fn run_pipeline(&mut self, pipeline: &mut Pipeline, noalias: bool) -> Option<i32> {
-        pipeline.expand(&self.variables, &self.directory_stack);
+        if let Err(variable) = pipeline.expand(&self.variables, &self.directory_stack, self.flags & NO_UNSET != 0) {
+            self.undefined_variable(&variable);
+            return Some(FAILURE);
+        }
 
-        let command_start_time = SystemTime::now();
+        if self.flags & PRINT_COMMS != 0 {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "{}{}", self.variables.get_var_or_empty("XTRACE_PREFIX"), pipeline);
+        }
 
         let mut exit_status = None;
+        // Whether the status was given by the final command, and not by one followed by `&&` or `||`.
+        let mut last_command = true;
         let mut branched = false;
         let builtins = self.builtins;
 
@@ -319,28 +605,57 @@ impl<'a> Shell<'a> {
         }
 
         if !branched {
+            // A builtin which ends a pipeline reads the output of the commands that are piped into it.
+            let piped = match pipeline.jobs.len() {
+                length if length > 1 && builtins.contains_key(pipeline.jobs[length - 1].command.as_str()) => {
+                    match pipeline.jobs[length - 2].kind {
+                        JobKind::Pipe(from) => Some(from),
+                        _                   => None
+                    }
+                },
+                _ => None
+            };
+
+            // Where pipes are not supported, the builtin is executed as any other command would be.
+            let piped = piped.and_then(|from| sys::pipe().ok().map(|pipe| (from, pipe)));
+
             // Branch if -> input == shell command i.e. echo
-            exit_status = if let Some(command) = builtins.get(pipeline.jobs[0].command.as_str()) {
-                // Run the 'main' of the command and set exit_status
-                Some((*command.main)(pipeline.jobs[0].args.as_slice(), self))
+            exit_status = if let Some((from, (reader, writer))) = piped {
+                Some(self.pipe_into_builtin(pipeline, from, reader, writer))
+            } else if let Some(command) = builtins.get(pipeline.jobs[0].command.as_str()) {
+                let input = match pipeline.stdin {
+                    Some(ref stdin) => File::open(&stdin.file).map(Some).map_err(|why| (stdin.file.as_str(), why)),
+                    None            => Ok(None)
+                };
+                match input {
+                    Ok(input) => {
+                        self.stdin = input;
+                        // Run the 'main' of the command and set exit_status
+                        let status = (*command.main)(pipeline.jobs[0].args.as_slice(), self);
+                        self.stdin = None;
+                        Some(status)
+                    },
+                    Err((file, why)) => {
+                        let stderr = io::stderr();
+                        let _ = writeln!(stderr.lock(), "ion: failed to redirect stdin into {}: {}", file, why);
+                        Some(FAILURE)
+                    }
+                }
             // Branch else if -> input == shell function and set the exit_status
             } else if let Some(function) = self.functions.get(pipeline.jobs[0].command.as_str()).cloned() {
                 if pipeline.jobs[0].args.len() - 1 == function.args.len() {
-                    let mut variables_backup: HashMap<&str, Option<String>> = HashMap::new();
+                    // Each function call receives its own scope, so that its arguments and any
+                    // variables it defines are dropped when the function returns.
+                    self.variables.new_scope();
                     for (name, value) in function.args.iter().zip(pipeline.jobs[0].args.iter().skip(1)) {
-                        variables_backup.insert(name, self.variables.get_var(name));
                         self.variables.set_var(name, value);
                     }
 
+                    self.previous_status = SUCCESS;
                     self.execute_statements(function.statements);
-
-                    for (name, value_option) in &variables_backup {
-                        match *value_option {
-                            Some(ref value) => self.variables.set_var(name, value),
-                            None => {self.variables.unset_var(name);},
-                        }
-                    }
-                    None
+                    self.variables.pop_scope();
+                    // The function exits with the status of the last statement that it ran.
+                    Some(self.previous_status)
                 } else {
                     let stderr = io::stderr();
                     let mut stderr = stderr.lock();
@@ -350,33 +665,74 @@ impl<'a> Shell<'a> {
                 }
             // If not a shell command or a shell function execute the pipeline and set the exit_status
             } else {
-                Some(execute_pipeline(pipeline))
+                self.locate_commands(pipeline);
+                let (status, last) = execute_pipeline(pipeline);
+                last_command = last;
+                Some(status)
             };
         }
 
-        if let Ok(elapsed_time) = command_start_time.elapsed() {
-            let summary = format!("#summary# elapsed real time: {}.{:09} seconds",
-                                  elapsed_time.as_secs(), elapsed_time.subsec_nanos());
-
-            // If `RECORD_SUMMARY` is set to "1" (True, Yes), then write a summary of the pipline
-            // just executed to the the file and context histories. At the moment, this means
-            // record how long it took.
-            if "1" == self.variables.get_var_or_empty("RECORD_SUMMARY") {
-                self.context.history.push(summary.into()).unwrap_or_else(|err| {
-                    let stderr = io::stderr();
-                    let mut stderr = stderr.lock();
-                    let _ = writeln!(stderr, "ion: {}\n", err);
-                });
-            }
-        }
-
         // Retrieve the exit_status and set the $? variable and history.previous_status
         if let Some(code) = exit_status {
-            self.variables.set_var("?", &code.to_string());
+            self.variables.set_global_var("?", &code.to_string());
             self.previous_status = code;
+            if code != SUCCESS && last_command && self.flags & ERR_EXIT != 0 {
+                process::exit(code);
+            }
         }
         exit_status
     }
 
 
 }
+
+/// Reads a script from the standard input, printing an error if it could not be read.
+fn read_stdin() -> Option<String> {
+    let mut script = String::new();
+    match io::stdin().read_to_string(&mut script) {
+        Ok(_) => Some(script),
+        Err(err) => {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: failed to read the standard input: {}", err);
+            None
+        }
+    }
+}
+
+/// Reads the script at the given path, printing an error if it could not be read.
+fn read_script(path: &str) -> Option<String> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: failed to open {}: {}", path, err);
+            return None;
+        }
+    };
+
+    let capacity = file.metadata().ok().map_or(0, |x| x.len());
+    let mut script = String::with_capacity(capacity as usize);
+    match file.read_to_string(&mut script) {
+        Ok(_) => Some(script),
+        Err(err) => {
+            let stderr = io::stderr();
+            let _ = writeln!(stderr.lock(), "ion: failed to read {}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Reads a line with the editor, as `Context::read_line_with_init_buffer` does, except that the
+/// Emacs key bindings are extended to search the history, and the buffer is drawn with the colour
+/// closure.
+fn read_line(context: &mut Context, prompt: String, buffer: String, colour: ColorClosure, hint: Rc<RefCell<Hint>>,
+    handler: &mut EventHandler<RawTerminal<Stdout>>) -> io::Result<String>
+{
+    let line = {
+        let stdout = io::stdout().into_raw_mode()?;
+        let editor = Editor::new_with_init_buffer(stdout, prompt, Some(colour), context, buffer)?;
+        Context::handle_keys(SearchKeyMap::new(Emacs::new(editor), hint), handler)
+    };
+    context.revert_all_history();
+    line
+}
@@ -0,0 +1,152 @@
+//! Parses the command-line arguments that the shell was invoked with.
+
+use super::flags;
+
+pub const USAGE: &'static str = "ion
+
+USAGE:
+    ion [OPTIONS] [SCRIPT | -] [ARGS...]
+    ion [OPTIONS] -c COMMAND [ARGS...]
+
+OPTIONS:
+    -c COMMAND      Execute the given command instead of a script
+    -n              Check the script for syntax errors without executing it
+    -e              Exit when a command fails, as with `set -e`
+    -u              Treat undefined variables as errors, as with `set -u`
+    -x              Print each command after it has been expanded, as with `set -x`
+    -i              Run interactively, even if the standard input is not a terminal
+    -l              Run as a login shell, which is accepted for compatibility with login programs
+    --parse-tree    Print the tree of statements parsed from the script
+    --version       Print the version of the shell
+    --help          Print this help message
+
+If neither a script nor a command is given, and the standard input is not a terminal, or if the
+script is given as `-`, the script will be read from the standard input. Any arguments following
+the script or command are supplied to it as the `@args` array, and as `$1`, `$2`, and so on.";
+
+/// The options and arguments given to the shell on the command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct ShellOptions {
+    /// A command to execute, given by `-c`.
+    pub command:     Option<String>,
+    /// The path of a script to execute, where `-` denotes the standard input.
+    pub script:      Option<String>,
+    /// The arguments that follow the script or command.
+    pub args:        Vec<String>,
+    pub noexec:      bool,
+    /// The options given by their short flags, such as `-x`, as they would be given to `set`.
+    pub flags:       u8,
+    pub interactive: bool,
+    pub parse_tree:  bool,
+    pub version:     bool,
+    pub help:        bool,
+}
+
+impl ShellOptions {
+    /// Parses the given arguments, excluding the name of the executable. Options may be combined,
+    /// such as `-nx`. The first argument that is not an option is the script, unless a command
+    /// was given, and every argument after it is supplied to the script or command.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<ShellOptions, String> {
+        let mut options = ShellOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" { break }
+
+            // A lone `-` is not an option, as it denotes that the script is the standard input.
+            if !arg.starts_with('-') || arg == "-" {
+                if options.command.is_some() {
+                    options.args.push(arg);
+                } else {
+                    options.script = Some(arg);
+                }
+                break
+            }
+
+            match arg.as_str() {
+                "--parse-tree" => options.parse_tree = true,
+                "--version"    => options.version = true,
+                "--help"       => options.help = true,
+                _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+                _ => {
+                    for flag in arg.chars().skip(1) {
+                        match flag {
+                            'c' => match args.next() {
+                                Some(command) => options.command = Some(command),
+                                None          => return Err("option requires an argument: -c".to_owned())
+                            },
+                            'n' => options.noexec = true,
+                            'i' => options.interactive = true,
+                            // The init file is evaluated regardless of whether the shell is a login
+                            // shell, so this is only accepted for the sake of login programs.
+                            'l' => (),
+                            _   => match flags::from_short(flag) {
+                                Some(flag) => options.flags |= flag,
+                                None       => return Err(format!("unknown option: -{}", flag))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Arguments following a `--` may still name the script.
+        if options.command.is_none() && options.script.is_none() {
+            options.script = args.next();
+        }
+        options.args.extend(args);
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_helpers::strings;
+
+    fn parse(args: &[&str]) -> Result<ShellOptions, String> {
+        ShellOptions::parse(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    #[test]
+    fn script_arguments() {
+        let options = parse(&["-n", "script.ion", "-x", "two"]).unwrap();
+        assert_eq!(options.script, Some("script.ion".to_owned()));
+        assert_eq!(options.args, strings(&["-x", "two"]));
+        assert!(options.noexec);
+        assert_eq!(options.flags, 0);
+
+        let options = parse(&["--", "-script.ion", "one"]).unwrap();
+        assert_eq!(options.script, Some("-script.ion".to_owned()));
+        assert_eq!(options.args, strings(&["one"]));
+
+        let options = parse(&["-", "one"]).unwrap();
+        assert_eq!(options.script, Some("-".to_owned()));
+        assert_eq!(options.args, strings(&["one"]));
+    }
+
+    #[test]
+    fn command_arguments() {
+        let options = parse(&["-xc", "echo $1", "one", "two"]).unwrap();
+        assert_eq!(options.command, Some("echo $1".to_owned()));
+        assert_eq!(options.script, None);
+        assert_eq!(options.args, strings(&["one", "two"]));
+        assert_eq!(options.flags, flags::PRINT_COMMS);
+
+        let options = parse(&["-eu", "script.ion"]).unwrap();
+        assert_eq!(options.flags, flags::ERR_EXIT | flags::NO_UNSET);
+    }
+
+    #[test]
+    fn combined_flags() {
+        let options = parse(&["-il", "--parse-tree", "--version", "--help"]).unwrap();
+        assert!(options.interactive && options.parse_tree && options.version && options.help);
+        assert_eq!(options.script, None);
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(parse(&["-c"]).is_err());
+        assert!(parse(&["-z"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+}
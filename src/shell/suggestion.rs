@@ -0,0 +1,108 @@
+//! Suggests how the command being typed may be finished, from the commands within the history.
+
+use std::path::Path;
+
+use termion::style;
+
+use super::history_record::HistoryRecord;
+
+/// Finds the most recent command within the history which begins with the buffer, and returns the
+/// remainder of that command. Commands which were run within the current directory are preferred
+/// over those which were run elsewhere. Commands spanning multiple lines are never suggested, as
+/// the suggestion is displayed after the cursor.
+pub fn suggest(buffer: &str, history: &[HistoryRecord], cwd: &Path) -> Option<String> {
+    if buffer.trim().is_empty() { return None }
+
+    let mut candidates = history.iter().rev()
+        .filter(|record| record.command.len() > buffer.len() && record.command.starts_with(buffer))
+        .filter(|record| !record.command.contains('\n'));
+
+    let newest = match candidates.next() {
+        Some(record) => record,
+        None         => return None
+    };
+    let record = if newest.cwd == cwd {
+        newest
+    } else {
+        candidates.find(|record| record.cwd == cwd).unwrap_or(newest)
+    };
+    Some(record.command[buffer.len()..].to_owned())
+}
+
+/// Text which is drawn in a faint style after the buffer, such as a suggestion or the state of a
+/// history search. It is drawn by the closure with which liner colours the buffer, and only while
+/// the buffer is the one which the text was given for, so that it never outlives an edit.
+#[derive(Debug, Default)]
+pub struct Hint {
+    line: String,
+    text: String,
+}
+
+impl Hint {
+    /// Gives the text to be drawn after the buffer, returning whether the hint has changed.
+    pub fn set(&mut self, buffer: &str, text: &str) -> bool {
+        let line = buffer.rsplit('\n').next().unwrap_or("");
+        if self.line == line && self.text == text { return false }
+        self.line = line.to_owned();
+        self.text = text.to_owned();
+        true
+    }
+
+    /// Removes the text, returning whether there was any.
+    pub fn clear(&mut self) -> bool {
+        let drawn = !self.text.is_empty();
+        self.set("", "");
+        drawn
+    }
+
+    /// The text as it is drawn after the line, if the line ends the buffer it was given for.
+    pub fn render(&self, line: &str) -> Option<String> {
+        if self.text.is_empty() || self.line != line { return None }
+        Some(format!("{}{}{}", style::Faint, self.text, style::Reset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn history(commands: &[(&str, &str)]) -> Vec<HistoryRecord> {
+        commands.iter().map(|&(command, cwd)| HistoryRecord {
+            cwd: PathBuf::from(cwd), ..HistoryRecord::unknown(command.to_owned())
+        }).collect()
+    }
+
+    #[test]
+    fn suggesting_recent_commands() {
+        let history = history(&[("git status", "/"), ("git stash", "/"), ("cargo build", "/"),
+            ("git stash pop\necho done", "/")]);
+        let cwd = Path::new("/home/ion");
+        assert_eq!(suggest("git st", &history, cwd), Some("ash".to_owned()));
+        assert_eq!(suggest("cargo", &history, cwd), Some(" build".to_owned()));
+        assert_eq!(suggest("cargo build", &history, cwd), None);
+        assert_eq!(suggest("make", &history, cwd), None);
+        assert_eq!(suggest("  ", &history, cwd), None);
+    }
+
+    #[test]
+    fn suggesting_commands_from_the_same_directory() {
+        let history = history(&[("make test", "/home/ion/project"), ("make install", "/home/ion"),
+            ("make clean", "/tmp")]);
+        assert_eq!(suggest("make", &history, Path::new("/home/ion/project")), Some(" test".to_owned()));
+        assert_eq!(suggest("make", &history, Path::new("/home/ion")), Some(" install".to_owned()));
+        assert_eq!(suggest("make", &history, Path::new("/usr")), Some(" clean".to_owned()));
+    }
+
+    #[test]
+    fn hints_are_only_drawn_after_their_buffer() {
+        let mut hint = Hint::default();
+        assert!(hint.set("git st", "ash"));
+        assert!(!hint.set("git st", "ash"));
+        assert_eq!(hint.render("git st"), Some(format!("{}ash{}", style::Faint, style::Reset)));
+        assert_eq!(hint.render("git s"), None);
+        assert!(hint.clear());
+        assert_eq!(hint.render(""), None);
+        assert!(!hint.clear());
+    }
+}
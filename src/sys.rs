@@ -0,0 +1,262 @@
+//! Thin wrappers around the platform-specific functionality that the standard library lacks.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+#[cfg(all(unix, not(target_os = "redox")))]
+pub const STDIN_FILENO: i32 = ::libc::STDIN_FILENO;
+
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub const STDIN_FILENO: i32 = 0;
+
+/// Returns `true` if the given file descriptor refers to a terminal.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn isatty(fd: i32) -> bool {
+    unsafe { ::libc::isatty(fd) == 1 }
+}
+
+/// Platforms without `isatty` are assumed to always be interactive.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn isatty(_fd: i32) -> bool {
+    true
+}
+
+/// Blocks until the file is locked, either exclusively or shared with other readers. The lock is
+/// released once the file is closed.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn lock_file(file: &fs::File, exclusive: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let operation = if exclusive { ::libc::LOCK_EX } else { ::libc::LOCK_SH };
+    if unsafe { ::libc::flock(file.as_raw_fd(), operation) } == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+/// Platforms without `flock` do not lock files.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn lock_file(_file: &fs::File, _exclusive: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// Returns `true` if the path refers to a file which may be executed.
+#[cfg(unix)]
+pub fn is_executable<P: AsRef<Path>>(path: P) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Platforms without permission bits consider every file to be executable.
+#[cfg(not(unix))]
+pub fn is_executable<P: AsRef<Path>>(path: P) -> bool {
+    fs::metadata(path).map(|metadata| metadata.is_file()).unwrap_or(false)
+}
+
+/// Makes the command execute the program at the given path, rather than searching `$PATH` for it,
+/// where `args` are every argument that the program receives, beginning with its name as it was
+/// typed. Should the program no longer exist at that path, `$PATH` is searched after all.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn execute_at(command: &mut Command, path: PathBuf, args: &[String]) {
+    use std::ffi::CString;
+    use std::os::raw::c_char;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    use std::ptr;
+
+    /// The arguments of `execv`, which are prepared before the process is forked, as memory
+    /// should not be allocated within the child.
+    struct Arguments {
+        path:     CString,
+        /// Owns the strings which the pointers refer to.
+        _strings: Vec<CString>,
+        pointers: Vec<*const c_char>,
+    }
+    // The pointers are only read, and refer to strings which are owned alongside them.
+    unsafe impl Send for Arguments {}
+    unsafe impl Sync for Arguments {}
+
+    let path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(_)   => return
+    };
+    let strings = match args.iter().map(|arg| CString::new(arg.as_bytes())).collect::<Result<Vec<_>, _>>() {
+        Ok(strings) => strings,
+        Err(_)      => return
+    };
+    let mut pointers: Vec<*const c_char> = strings.iter().map(|string| string.as_ptr()).collect();
+    pointers.push(ptr::null());
+    let arguments = Arguments { path: path, _strings: strings, pointers: pointers };
+
+    command.before_exec(move || {
+        unsafe { ::libc::execv(arguments.path.as_ptr(), arguments.pointers.as_ptr()); }
+        // Returning successfully lets the command be executed by its name instead.
+        let error = io::Error::last_os_error();
+        if error.kind() == io::ErrorKind::NotFound { Ok(()) } else { Err(error) }
+    });
+}
+
+/// Other platforms execute the command by its name.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn execute_at(_command: &mut Command, _path: PathBuf, _args: &[String]) {}
+
+/// Creates a pipe, returning the end which is read from, and the end which is written to. Neither
+/// end is inherited by the programs that the shell executes, unless it is given to them.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn pipe() -> io::Result<(fs::File, fs::File)> {
+    use std::os::unix::io::FromRawFd;
+    use libc::{fcntl, FD_CLOEXEC, F_SETFD};
+
+    let mut fds = [0; 2];
+    if unsafe { ::libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let files = unsafe { (fs::File::from_raw_fd(fds[0]), fs::File::from_raw_fd(fds[1])) };
+    for &fd in &fds {
+        if unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(files)
+}
+
+/// Platforms without pipes may not create them.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn pipe() -> io::Result<(fs::File, fs::File)> {
+    Err(io::Error::new(io::ErrorKind::Other, "pipes are not supported"))
+}
+
+/// Forks the shell, with the standard output of the child written to the given file. The process
+/// ID of the child is returned to the parent, whereas the child is given 0.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn fork_into(output: &fs::File) -> io::Result<u32> {
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+
+    // Anything which has yet to be written would otherwise be written by both processes.
+    let _ = io::stdout().flush();
+    match unsafe { ::libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0  => {
+            if unsafe { ::libc::dup2(output.as_raw_fd(), ::libc::STDOUT_FILENO) } == -1 {
+                unsafe { ::libc::_exit(::status::FAILURE); }
+            }
+            Ok(0)
+        },
+        pid => Ok(pid as u32)
+    }
+}
+
+/// Platforms without `fork` may not fork the shell.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn fork_into(_output: &fs::File) -> io::Result<u32> {
+    Err(io::Error::new(io::ErrorKind::Other, "forking the shell is not supported"))
+}
+
+/// Waits for the process with the given ID to exit, returning its exit status.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn wait(pid: u32) -> io::Result<i32> {
+    let mut status = 0;
+    while unsafe { ::libc::waitpid(pid as ::libc::pid_t, &mut status, 0) } == -1 {
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted { return Err(error) }
+    }
+    Ok(exit_status(status))
+}
+
+/// The status is only given by a process which exited, rather than one ended by a signal.
+#[cfg(all(unix, not(target_os = "redox")))]
+fn exit_status(status: i32) -> i32 {
+    if status & 0x7f == 0 { (status >> 8) & 0xff } else { ::status::TERMINATED }
+}
+
+/// Platforms without `fork` have no processes to wait for.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn wait(_pid: u32) -> io::Result<i32> {
+    Err(io::Error::new(io::ErrorKind::Other, "waiting for processes is not supported"))
+}
+
+/// Kills the process with the given ID, without waiting for it to exit.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn kill(pid: u32) {
+    unsafe { ::libc::kill(pid as ::libc::pid_t, ::libc::SIGKILL); }
+}
+
+/// Platforms without `fork` have no processes to kill.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn kill(_pid: u32) {}
+
+/// Duplicates the standard input, so that it may be read from without being buffered, and
+/// without being closed once the duplicate is dropped.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn stdin() -> io::Result<fs::File> {
+    use std::os::unix::io::FromRawFd;
+    match unsafe { ::libc::dup(STDIN_FILENO) } {
+        -1 => Err(io::Error::last_os_error()),
+        fd => Ok(unsafe { fs::File::from_raw_fd(fd) })
+    }
+}
+
+/// Platforms without `dup` may not duplicate the standard input.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn stdin() -> io::Result<fs::File> {
+    Err(io::Error::new(io::ErrorKind::Other, "duplicating the standard input is not supported"))
+}
+
+/// Reads the output of the process with the given ID until the process exits, returning the
+/// output along with its exit status. Should the process leave a command running in the background
+/// which inherited the output, the command is not waited for, and its output is not read.
+#[cfg(all(unix, not(target_os = "redox")))]
+pub fn read_until_exit(output: &mut fs::File, pid: u32) -> io::Result<(Vec<u8>, i32)> {
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+    use libc::{fcntl, poll, pollfd, waitpid, F_GETFL, F_SETFL, O_NONBLOCK, POLLIN, WNOHANG};
+
+    let fd = output.as_raw_fd();
+    if unsafe { fcntl(fd, F_SETFL, fcntl(fd, F_GETFL) | O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    let mut status = None;
+    loop {
+        match output.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => {
+                buffer.extend_from_slice(&chunk[..read]);
+                continue
+            },
+            Err(ref why) if why.kind() == io::ErrorKind::Interrupted => continue,
+            Err(ref why) if why.kind() == io::ErrorKind::WouldBlock => (),
+            Err(why) => return Err(why),
+        }
+
+        // Everything that the process wrote has been read once it has exited and the pipe is empty.
+        if status.is_some() { break }
+        let mut raw = 0;
+        match unsafe { waitpid(pid as ::libc::pid_t, &mut raw, WNOHANG) } {
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted { return Err(error) }
+            },
+            0 => {
+                let mut readable = pollfd { fd: fd, events: POLLIN, revents: 0 };
+                unsafe { poll(&mut readable, 1, 10); }
+            },
+            _ => status = Some(exit_status(raw)),
+        }
+    }
+
+    match status {
+        Some(status) => Ok((buffer, status)),
+        None => wait(pid).map(|status| (buffer, status))
+    }
+}
+
+/// Platforms without pipes may not read the output of a process.
+#[cfg(not(all(unix, not(target_os = "redox"))))]
+pub fn read_until_exit(_output: &mut fs::File, _pid: u32) -> io::Result<(Vec<u8>, i32)> {
+    Err(io::Error::new(io::ErrorKind::Other, "reading the output of a process is not supported"))
+}
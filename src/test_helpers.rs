@@ -0,0 +1,14 @@
+//! Helpers which are shared between the unit tests of several modules.
+
+use parser::peg::Pipeline;
+use shell::{Job, JobKind};
+
+/// Converts string literals into owned strings, such as the arguments given to a builtin.
+pub fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|&arg| arg.to_owned()).collect()
+}
+
+/// A pipeline of a single command, as the parser reads it from the given words.
+pub fn pipeline(args: &[&str]) -> Pipeline {
+    Pipeline::new(vec![Job::new(strings(args), JobKind::Last)], None, None)
+}
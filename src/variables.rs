@@ -1,16 +1,27 @@
 use std::collections::HashMap;
 use std::env;
+use std::fs::File;
+use std::io::{self, Read};
 use std::path::PathBuf;
 use std::process;
 
 use directory_stack::DirectoryStack;
 use liner::Context;
 use status::{SUCCESS, FAILURE};
+use sys;
+
+/// A frame of local variables and arrays, such as the one created for each function call.
+#[derive(Default)]
+pub struct Scope {
+    pub arrays:    HashMap<String, Vec<String>>,
+    pub variables: HashMap<String, String>,
+}
 
 pub struct Variables {
     pub arrays:    HashMap<String, Vec<String>>,
     pub variables: HashMap<String, String>,
-    pub aliases:   HashMap<String, String>
+    pub aliases:   HashMap<String, String>,
+    scopes:        Vec<Scope>,
 }
 
 impl Default for Variables {
@@ -20,6 +31,13 @@ impl Default for Variables {
         map.insert("HISTORY_SIZE".into(), "1000".into());
         map.insert("HISTORY_FILE_ENABLED".into(), "0".into());
         map.insert("HISTORY_FILE_SIZE".into(), "1000".into());
+        map.insert("HISTORY_IGNORE_DUPS".into(), "0".into());
+        map.insert("HISTORY_ERASE_DUPS".into(), "0".into());
+        map.insert("HISTORY_IGNORE_SPACE".into(), "0".into());
+        map.insert("HISTORY_IGNORE".into(), "".into());
+        map.insert("HISTORY_SHARED".into(), "0".into());
+        map.insert("HISTORY_EXPANSION".into(), "1".into());
+        map.insert("XTRACE_PREFIX".into(), "+ ".into());
         map.insert("PROMPT".into(), "\x1B]0;${USER}: ${PWD}\x07\x1B[0m\x1B[1;38;5;85m${USER}\x1B[37m:\x1B[38;5;75m${PWD}\x1B[37m#\x1B[0m ".into());
 
         // Initialize the HISTORY_FILE variable
@@ -33,17 +51,48 @@ impl Default for Variables {
 
         // Initialize the HOME variable
         env::home_dir().map_or_else(|| env::set_var("HOME", "?"), |path| env::set_var("HOME", path.to_str().unwrap_or("?")));
-        Variables { arrays: HashMap::new(), variables: map, aliases: HashMap::new() }
+        Variables { arrays: HashMap::new(), variables: map, aliases: HashMap::new(), scopes: Vec::new() }
     }
 }
 
 impl Variables {
-    pub fn read<I: IntoIterator>(&mut self, args: I) -> i32
+    /// Reads a record for each of the variables given by `args`. Records are read from the `input`
+    /// which was redirected or piped into the builtin, or otherwise from the standard input if it
+    /// is not a terminal, so that `while read line` may be used to iterate over a file or the
+    /// output of a command. Otherwise, each variable is prompted for.
+    pub fn read<I: IntoIterator>(&mut self, args: I, input: Option<File>) -> i32
         where I::Item: AsRef<str>
     {
+        let args = args.into_iter().skip(1).collect::<Vec<I::Item>>();
+        let (nul_delimited, args) = match args.first().map(|arg| arg.as_ref()) {
+            Some("-0") => (true, &args[1..]),
+            _          => (false, &args[..]),
+        };
+
+        let input: Option<Box<Read>> = match input {
+            Some(input) => Some(Box::new(input)),
+            // Where the standard input may not be duplicated, it is read through its buffer instead.
+            None if nul_delimited || !sys::isatty(sys::STDIN_FILENO) => match sys::stdin() {
+                Ok(stdin) => Some(Box::new(stdin)),
+                Err(_)    => Some(Box::new(io::stdin()))
+            },
+            None => None
+        };
+
+        if let Some(mut input) = input {
+            let delimiter = if nul_delimited { b'\0' } else { b'\n' };
+            for arg in args {
+                match read_record(&mut input, delimiter) {
+                    Ok(Some(record)) => self.set_var(arg.as_ref(), String::from_utf8_lossy(&record).trim()),
+                    Ok(None) | Err(_) => return FAILURE,
+                }
+            }
+            return SUCCESS;
+        }
+
         let mut con = Context::new();
-        for arg in args.into_iter().skip(1) {
-            match con.read_line(format!("{}=", arg.as_ref().trim()), &mut |_| {}) {
+        for arg in args {
+            match con.read_line(format!("{}=", arg.as_ref().trim()), None, &mut |_| {}) {
                 Ok(buffer) => self.set_var(arg.as_ref(), buffer.trim()),
                 Err(_) => return FAILURE,
             }
@@ -51,31 +100,88 @@ impl Variables {
         SUCCESS
     }
 
+    /// Pushes a new local scope onto the scope stack. Until it is popped, variables and arrays
+    /// that are assigned will be stored within this scope rather than the global scope.
+    pub fn new_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    /// Drops the innermost local scope, along with every variable and array defined within it.
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Sets a variable within the innermost scope, or the global scope if no local scope exists.
+    /// An empty value removes a global variable, whereas a local variable is kept, so that it
+    /// shadows any outer variable of the same name.
     pub fn set_var(&mut self, name: &str, value: &str) {
-        if !name.is_empty() {
-            if value.is_empty() {
-                self.variables.remove(&name.to_string());
-            } else {
-                self.variables.insert(name.to_string(), value.to_string());
+        match self.scopes.last_mut() {
+            Some(scope) => set_or_remove(&mut scope.variables, name, value.to_owned(), false),
+            None        => set_or_remove(&mut self.variables, name, value.to_owned(), value.is_empty()),
+        }
+    }
+
+    /// Sets a variable within the innermost scope that defines it, so that a variable may be updated
+    /// from within a function. Variables which are not yet defined are set as with `set_var`.
+    pub fn update_var(&mut self, name: &str, value: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if scope.variables.contains_key(name) {
+                scope.variables.insert(name.to_owned(), value.to_owned());
+                return;
             }
         }
+        if self.variables.contains_key(name) {
+            set_or_remove(&mut self.variables, name, value.to_owned(), value.is_empty());
+        } else {
+            self.set_var(name, value);
+        }
     }
 
+    /// Sets a variable within the global scope, regardless of how many local scopes are active.
+    pub fn set_global_var(&mut self, name: &str, value: &str) {
+        set_or_remove(&mut self.variables, name, value.to_owned(), value.is_empty());
+    }
+
+    /// Sets an array within the innermost scope, or the global scope if no local scope exists. As
+    /// with variables, an empty local array shadows any outer array of the same name.
     pub fn set_array(&mut self, name: &str, value: Vec<String>) {
-        if !name.is_empty() {
-            if value.is_empty() {
-                self.arrays.remove(&name.to_string());
-            } else {
-                self.arrays.insert(name.to_string(), value);
+        match self.scopes.last_mut() {
+            Some(scope) => set_or_remove(&mut scope.arrays, name, value, false),
+            None        => {
+                let is_empty = value.is_empty();
+                set_or_remove(&mut self.arrays, name, value, is_empty)
             }
         }
     }
 
+    /// Sets an array within the global scope, regardless of how many local scopes are active.
+    pub fn set_global_array(&mut self, name: &str, value: Vec<String>) {
+        let is_empty = value.is_empty();
+        set_or_remove(&mut self.arrays, name, value, is_empty);
+    }
+
+    /// Searches for an array from the innermost scope outwards, ending with the global scope.
     pub fn get_array(&self, name: &str) -> Option<&Vec<String>> {
-        self.arrays.get(name)
+        self.scopes.iter().rev()
+            .filter_map(|scope| scope.arrays.get(name))
+            .next()
+            .or_else(|| self.arrays.get(name))
     }
 
+    /// Searches for a variable from the innermost scope outwards, followed by the global scope
+    /// and finally the process environment.
     pub fn get_var(&self, name: &str) -> Option<String> {
+        self.scopes.iter().rev()
+            .filter_map(|scope| scope.variables.get(name))
+            .next()
+            .or_else(|| self.variables.get(name))
+            .cloned()
+            .or_else(|| env::var(name).ok())
+    }
+
+    /// Obtains a variable from the global scope or the process environment, ignoring any
+    /// local values that may shadow it.
+    pub fn get_global_var(&self, name: &str) -> Option<String> {
         self.variables.get(name).cloned().or_else(|| env::var(name).ok())
     }
 
@@ -83,12 +189,49 @@ impl Variables {
         self.get_var(name).unwrap_or_default()
     }
 
-    pub fn unset_var(&mut self, name: &str) -> Option<String> {
-        self.variables.remove(name)
+    /// Removes the variable, or the array, from the innermost scope that defines it, returning
+    /// whether one was found.
+    pub fn unset_var(&mut self, name: &str) -> bool {
+        for scope in self.scopes.iter_mut().rev() {
+            if scope.variables.remove(name).is_some() || scope.arrays.remove(name).is_some() {
+                return true;
+            }
+        }
+        self.variables.remove(name).is_some() || self.arrays.remove(name).is_some()
+    }
+
+    /// The variables which are visible from the innermost scope, where local variables take the
+    /// place of the outer variables that they shadow.
+    pub fn visible_variables(&self) -> HashMap<String, String> {
+        let mut variables = self.variables.clone();
+        for scope in &self.scopes {
+            variables.extend(scope.variables.iter().map(|(name, value)| (name.clone(), value.clone())));
+        }
+        variables
+    }
+
+    /// The arrays which are visible from the innermost scope, as with `visible_variables`.
+    pub fn visible_arrays(&self) -> HashMap<String, Vec<String>> {
+        let mut arrays = self.arrays.clone();
+        for scope in &self.scopes {
+            arrays.extend(scope.arrays.iter().map(|(name, value)| (name.clone(), value.clone())));
+        }
+        arrays
     }
 
     pub fn get_vars(&self) -> Vec<String> {
-        self.variables.keys().cloned().chain(env::vars().map(|(k, _)| k)).collect()
+        self.scopes.iter()
+            .flat_map(|scope| scope.variables.keys().cloned())
+            .chain(self.variables.keys().cloned())
+            .chain(env::vars().map(|(k, _)| k))
+            .collect()
+    }
+
+    pub fn get_arrays(&self) -> Vec<String> {
+        self.scopes.iter()
+            .flat_map(|scope| scope.arrays.keys().cloned())
+            .chain(self.arrays.keys().cloned())
+            .collect()
     }
 
     pub fn is_valid_variable_character(c: char) -> bool {
@@ -192,6 +335,34 @@ impl Variables {
     }
 }
 
+/// Inserts the value into the map, or removes the key if the value is empty.
+fn set_or_remove<T>(map: &mut HashMap<String, T>, name: &str, value: T, is_empty: bool) {
+    if !name.is_empty() {
+        if is_empty {
+            map.remove(name);
+        } else {
+            map.insert(name.to_owned(), value);
+        }
+    }
+}
+
+/// Reads a record from the input, up to a delimiter which is discarded, or `None` if the input has
+/// ended. The input is read a byte at a time, so that nothing after the delimiter is consumed, and
+/// is instead left for whichever command reads the input next.
+fn read_record<R: Read>(input: &mut R, delimiter: u8) -> io::Result<Option<Vec<u8>>> {
+    let mut record = Vec::new();
+    let mut byte = [0; 1];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => return Ok(if record.is_empty() { None } else { Some(record) }),
+            Ok(_) if byte[0] == delimiter => return Ok(Some(record)),
+            Ok(_) => record.push(byte[0]),
+            Err(ref why) if why.kind() == io::ErrorKind::Interrupted => (),
+            Err(why) => return Err(why),
+        }
+    }
+}
+
 #[cfg(all(unix, not(target_os = "redox")))]
 fn get_user_home(username: &str) -> Option<String> {
     use users_unix::get_user_by_name;
@@ -219,7 +390,7 @@ fn get_user_home(_username: &str) -> Option<String> {
 mod tests {
     use super::*;
     use directory_stack::DirectoryStack;
-    use parser::expand_string;
+    use parser::{expand_defined, expand_string};
 
     fn new_dir_stack() -> DirectoryStack {
         DirectoryStack::new().unwrap()
@@ -232,6 +403,14 @@ mod tests {
         assert_eq!("", &expanded);
     }
 
+    #[test]
+    fn undefined_variables_are_reported_when_checked() {
+        let mut variables = Variables::default();
+        variables.set_var("FOO", "BAR");
+        assert_eq!(expand_defined("$FOO", &variables, &new_dir_stack(), false), Ok(vec!["BAR".to_owned()]));
+        assert_eq!(expand_defined("$FOO${BAZ}$QUX", &variables, &new_dir_stack(), false), Err("BAZ".to_owned()));
+    }
+
     #[test]
     fn set_var_and_expand_a_variable() {
         let mut variables = Variables::default();
@@ -239,4 +418,78 @@ mod tests {
         let expanded = expand_string("$FOO", &variables, &new_dir_stack(), false).join("");
         assert_eq!("BAR", &expanded);
     }
+
+    #[test]
+    fn local_variables_do_not_leak_from_scope() {
+        let mut variables = Variables::default();
+        variables.set_var("FOO", "global");
+        variables.new_scope();
+        variables.set_var("FOO", "local");
+        variables.set_var("BAR", "local");
+        assert_eq!(Some("local".to_owned()), variables.get_var("FOO"));
+        variables.pop_scope();
+        assert_eq!(Some("global".to_owned()), variables.get_var("FOO"));
+        assert_eq!(None, variables.get_var("BAR"));
+    }
+
+    #[test]
+    fn outer_variables_are_visible_within_scope() {
+        let mut variables = Variables::default();
+        variables.set_var("FOO", "outer");
+        variables.set_array("ARRAY", vec!["one".to_owned(), "two".to_owned()]);
+        variables.new_scope();
+        assert_eq!(Some("outer".to_owned()), variables.get_var("FOO"));
+        assert_eq!(Some(&vec!["one".to_owned(), "two".to_owned()]), variables.get_array("ARRAY"));
+    }
+
+    #[test]
+    fn global_assignment_from_within_scope() {
+        let mut variables = Variables::default();
+        variables.new_scope();
+        variables.set_var("FOO", "local");
+        variables.set_global_var("FOO", "global");
+        assert_eq!(Some("local".to_owned()), variables.get_var("FOO"));
+        variables.pop_scope();
+        assert_eq!(Some("global".to_owned()), variables.get_var("FOO"));
+    }
+
+    #[test]
+    fn empty_local_variables_shadow_outer_variables() {
+        let mut variables = Variables::default();
+        variables.set_var("FOO", "global");
+        variables.set_array("ARRAY", vec!["one".to_owned()]);
+        variables.new_scope();
+        variables.set_var("FOO", "");
+        variables.set_array("ARRAY", Vec::new());
+        assert_eq!(Some(String::new()), variables.get_var("FOO"));
+        assert_eq!(Some(&Vec::new()), variables.get_array("ARRAY"));
+        assert_eq!(Some(&String::new()), variables.visible_variables().get("FOO"));
+        variables.pop_scope();
+        assert_eq!(Some("global".to_owned()), variables.get_var("FOO"));
+        variables.set_var("FOO", "");
+        assert_eq!(None, variables.variables.get("FOO"));
+    }
+
+    #[test]
+    fn unsetting_local_arrays() {
+        let mut variables = Variables::default();
+        variables.set_array("ARRAY", vec!["global".to_owned()]);
+        variables.new_scope();
+        variables.set_array("ARRAY", vec!["local".to_owned()]);
+        assert!(variables.unset_var("ARRAY"));
+        assert_eq!(Some(&vec!["global".to_owned()]), variables.get_array("ARRAY"));
+        assert!(variables.unset_var("ARRAY"));
+        assert!(!variables.unset_var("ARRAY"));
+        assert_eq!(None, variables.get_array("ARRAY"));
+    }
+
+    #[test]
+    fn reading_records() {
+        let mut input = io::Cursor::new(b"one\ntwo\0three".to_vec());
+        assert_eq!(Some(b"one".to_vec()), read_record(&mut input, b'\n').unwrap());
+        assert_eq!(4, input.position());
+        assert_eq!(Some(b"two".to_vec()), read_record(&mut input, b'\0').unwrap());
+        assert_eq!(Some(b"three".to_vec()), read_record(&mut input, b'\n').unwrap());
+        assert_eq!(None, read_record(&mut input, b'\n').unwrap());
+    }
 }